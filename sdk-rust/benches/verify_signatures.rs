@@ -0,0 +1,53 @@
+//! Serial vs parallel channel-state signature verification
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use ethers::{
+    signers::{LocalWallet, Signer},
+    types::{Address, U256},
+};
+use synapse_sdk::{
+    channel_state_digest, channel_state_hash, verify_channel_state_signature, verify_channel_state_signatures,
+    ChannelDomain, ChannelSignatureItem,
+};
+
+fn domain() -> ChannelDomain {
+    ChannelDomain { chain_id: 8453, contract: Address::from_low_u64_be(0xc4a) }
+}
+
+fn signed_items(count: u64) -> Vec<ChannelSignatureItem> {
+    let wallet: LocalWallet = "0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318"
+        .parse()
+        .unwrap();
+    let channel_id = [7u8; 32];
+    
+    (0..count)
+        .map(|nonce| {
+            let (balance1, balance2, nonce) = (U256::from(1_000 - nonce), U256::from(nonce), U256::from(nonce));
+            let hash = channel_state_hash(domain(), channel_id, balance1, balance2, nonce);
+            let signature = wallet.sign_hash(channel_state_digest(hash)).unwrap();
+            (wallet.address(), channel_id, balance1, balance2, nonce, signature.to_vec().into())
+        })
+        .collect()
+}
+
+fn bench_verify(c: &mut Criterion) {
+    let items = signed_items(512);
+    
+    c.bench_function("verify_512_serial", |b| {
+        b.iter(|| {
+            black_box(&items)
+                .iter()
+                .map(|(signer, id, b1, b2, nonce, sig)| {
+                    verify_channel_state_signature(domain(), *signer, *id, *b1, *b2, *nonce, sig)
+                })
+                .collect::<Vec<bool>>()
+        })
+    });
+    
+    c.bench_function("verify_512_parallel", |b| {
+        b.iter(|| verify_channel_state_signatures(domain(), black_box(&items)))
+    });
+}
+
+criterion_group!(benches, bench_verify);
+criterion_main!(benches);
@@ -7,21 +7,31 @@ use ethers::{
     prelude::*,
     providers::{Http, Provider, Middleware},
     signers::{LocalWallet, Signer},
-    types::{Address, H256, U256, Bytes},
-    contract::abigen,
+    types::{Address, H256, U256, Bytes, transaction::eip2718::TypedTransaction},
+    contract::{abigen, parse_log, ContractCall, EthLogDecode, LogMeta},
+    abi::Detokenize,
 };
-use std::sync::Arc;
+use futures::{Stream, StreamExt};
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use thiserror::Error;
 use serde::{Deserialize, Serialize};
 
+pub mod scenarios;
+
 // Generate contract bindings
 abigen!(
     SynapseToken,
     r#"[
         function balanceOf(address account) external view returns (uint256)
         function transfer(address to, uint256 amount) external returns (bool)
+        function transferFrom(address from, address to, uint256 amount) external returns (bool)
         function approve(address spender, uint256 amount) external returns (bool)
         function allowance(address owner, address spender) external view returns (uint256)
+        function DOMAIN_SEPARATOR() external view returns (bytes32)
+        function transferWithAuthorization(address from, address to, uint256 value, uint256 validAfter, uint256 validBefore, bytes32 nonce, uint8 v, bytes32 r, bytes32 s) external
         event Transfer(address indexed from, address indexed to, uint256 value)
     ]"#
 );
@@ -35,9 +45,20 @@ abigen!(
         function releaseEscrow(bytes32 escrowId) external returns (bool)
         function refundEscrow(bytes32 escrowId) external returns (bool)
         function createStream(address recipient, uint256 totalAmount, uint256 startTime, uint256 endTime, bytes32 streamId) external returns (bool)
+        function escrows(bytes32) external view returns (address sender, address recipient, address arbiter, uint256 amount, uint256 deadline, uint8 status)
+        function cancelStream(bytes32 streamId) external returns (uint256 refunded)
+        function withdrawFromStream(bytes32 streamId) external
+        function getStreamBalance(bytes32 streamId) external view returns (uint256)
+        function streams(bytes32) external view returns (address sender, address recipient, uint256 totalAmount, uint256 withdrawn, uint256 startTime, uint256 endTime, bool active)
+        function baseFee() external view returns (uint256)
+        function tierDiscounts(uint8 tier) external view returns (uint256)
+        function synxToken() external view returns (address)
+        function reputationRegistry() external view returns (address)
         event Payment(address indexed sender, address indexed recipient, uint256 amount, uint256 fee, bytes32 paymentId)
         event EscrowCreated(bytes32 indexed escrowId, address indexed sender, address indexed recipient, uint256 amount, uint256 deadline)
         event StreamCreated(bytes32 indexed streamId, address indexed sender, address indexed recipient, uint256 totalAmount, uint256 startTime, uint256 endTime)
+        event StreamWithdrawal(bytes32 indexed streamId, address indexed recipient, uint256 amount)
+        event StreamCancelled(bytes32 indexed streamId, uint256 refundAmount)
     ]"#
 );
 
@@ -50,6 +71,14 @@ abigen!(
         function decreaseStake(uint256 amount) external returns (bool)
         function getTier(address agent) external view returns (uint8)
         function getSuccessRate(address agent) external view returns (uint256)
+        function registrationFee() external view returns (uint256)
+        function tierRequirements(uint8 tier) external view returns (uint256 minTransactions, uint256 minSuccessRate, uint256 minStake, uint256 feeDiscount)
+        function minStake() external view returns (uint256)
+        function nameToAgent(string name) external view returns (address)
+        function withdrawalCooldownEnd(address agent) external view returns (uint256)
+        function setOperator(address operator) external returns (bool)
+        function operatorOf(address agent) external view returns (address)
+        function synxToken() external view returns (address)
         function agents(address) external view returns (bool registered, string memory name, uint256 stake, uint256 reputationScore, uint256 totalTransactions, uint256 successfulTransactions, uint256 registeredAt, string memory metadataUri)
         event AgentRegistered(address indexed agent, string name, uint256 stake)
         event ReputationUpdated(address indexed agent, uint256 oldScore, uint256 newScore)
@@ -64,9 +93,13 @@ abigen!(
         function deactivateService(bytes32 serviceId) external returns (bool)
         function activateService(bytes32 serviceId) external returns (bool)
         function getServicesByCategory(string category) external view returns (bytes32[] memory)
+        function getServicesByProvider(address provider) external view returns (bytes32[] memory)
+        function synxToken() external view returns (address)
         function calculatePrice(bytes32 serviceId, uint256 quantity) external view returns (uint256)
         function services(bytes32) external view returns (address provider, string memory name, string memory category, string memory description, string memory endpoint, uint256 basePrice, uint8 pricingModel, bool active, uint256 totalRequests, uint256 totalRevenue, uint256 createdAt)
         event ServiceRegistered(bytes32 indexed serviceId, address indexed provider, string name, string category)
+        event ServiceRequest(bytes32 indexed serviceId, address indexed requester, uint256 amount)
+        event ServiceUpdated(bytes32 indexed serviceId, uint256 newPrice, uint8 newStatus)
     ]"#
 );
 
@@ -80,9 +113,22 @@ abigen!(
         function challengeClose(address counterparty, uint256 balance1, uint256 balance2, uint256 nonce, bytes sig1, bytes sig2) external returns (bool)
         function finalizeClose(address counterparty) external returns (bool)
         function getChannelId(address party1, address party2) external pure returns (bytes32)
+        function challengePeriod() external view returns (uint256)
+        function synxToken() external view returns (address)
         function channels(bytes32) external view returns (address participant1, address participant2, uint256 balance1, uint256 balance2, uint256 nonce, uint8 status, uint256 challengeEnd)
         event ChannelOpened(bytes32 indexed channelId, address indexed party1, address indexed party2, uint256 deposit1, uint256 deposit2)
+        event ChannelDeposit(bytes32 indexed channelId, address indexed party, uint256 amount)
+        event ChannelCloseInitiated(bytes32 indexed channelId, address indexed initiator, uint256 balanceA, uint256 balanceB, uint256 nonce)
+        event ChannelChallenged(bytes32 indexed channelId, address indexed challenger, uint256 newNonce)
         event ChannelClosed(bytes32 indexed channelId, uint256 finalBalance1, uint256 finalBalance2)
+        event ChannelDisputed(bytes32 indexed channelId)
+    ]"#
+);
+
+abigen!(
+    SynapseBatcher,
+    r#"[
+        function execute(address[] targets, bytes[] data) external returns (bytes[] results)
     ]"#
 );
 
@@ -95,6 +141,9 @@ pub enum SynapseError {
     #[error("Contract error: {0}")]
     ContractError(String),
     
+    #[error("Contract call reverted: {0}")]
+    CallReverted(String),
+    
     #[error("Wallet error: {0}")]
     WalletError(#[from] ethers::signers::WalletError),
     
@@ -107,22 +156,79 @@ pub enum SynapseError {
     #[error("Service not found: {0}")]
     ServiceNotFound(String),
     
+    #[error("Caller is not the provider of service {0}")]
+    NotServiceOwner(String),
+    
     #[error("Channel not found")]
     ChannelNotFound,
     
+    #[error("Stream not found")]
+    StreamNotFound,
+    
+    #[error("Escrow not found")]
+    EscrowNotFound,
+    
+    #[error("Caller is not the stream sender")]
+    NotStreamSender,
+    
     #[error("Invalid signature")]
     InvalidSignature,
     
+    #[error("Payment not found on-chain: {0}")]
+    PaymentNotFound(String),
+    
+    #[error("Stale channel state: nonce {received} does not exceed accepted nonce {current}")]
+    StaleState { current: U256, received: U256 },
+    
     #[error("Transaction failed: {0}")]
     TransactionFailed(String),
     
     #[error("Configuration error: {0}")]
     ConfigError(String),
+    
+    #[error("Timed out: {0}")]
+    Timeout(String),
+    
+    #[error("{} of {} streams failed", .failed.len(), .failed.len() + .created.len())]
+    PartialStreamFailure {
+        /// Streams that were created
+        created: Vec<StreamResult>,
+        /// Index into the requested specs and the reason each one failed
+        failed: Vec<(usize, String)>,
+    },
+    
+    #[error("Spend failed after funding from the payer in {funding_tx:?}: {source}")]
+    FundedSpendFailed {
+        /// `transferFrom` that moved the funds from the payer to this wallet
+        funding_tx: H256,
+        source: Box<SynapseError>,
+    },
+    
+    #[error("Recipient received {received}, expected {expected}")]
+    NetAmountMismatch { expected: U256, received: U256 },
+    
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
+    
+    #[error("Metadata error: {0}")]
+    MetadataError(String),
+    
+    #[error("Encryption error: {0}")]
+    EncryptionError(String),
 }
 
 /// Result type alias
 pub type Result<T> = std::result::Result<T, SynapseError>;
 
+/// Wrap an error from a spend in `FundedSpendFailed` when `funding_tx`
+/// pulled its funds from the payer
+fn funded(funding_tx: Option<H256>) -> impl FnOnce(SynapseError) -> SynapseError {
+    move |source| match funding_tx {
+        Some(funding_tx) => SynapseError::FundedSpendFailed { funding_tx, source: Box::new(source) },
+        None => source,
+    }
+}
+
 /// Reputation tier levels
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Tier {
@@ -148,6 +254,155 @@ impl From<u8> for Tier {
     }
 }
 
+/// Requirements an agent must meet to hold a tier
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TierRequirement {
+    pub tier: Tier,
+    pub min_transactions: U256,
+    /// Minimum success rate in basis points (9500 = 95%)
+    pub min_success_rate_bps: U256,
+    pub min_stake: U256,
+    pub fee_discount_bps: U256,
+}
+
+/// Reputation score points lost for a failed transaction (before the
+/// amount-based component)
+pub const FAILURE_SCORE_PENALTY: u64 = 50;
+
+/// Tier an agent with the given stats qualifies for, mirroring the
+/// registry's `_updateTier`: the highest tier whose requirements are all met
+pub fn project_tier(
+    total_transactions: U256,
+    successful_transactions: U256,
+    stake: U256,
+    requirements: &[TierRequirement],
+) -> Tier {
+    let success_rate_bps = if total_transactions.is_zero() {
+        U256::zero()
+    } else {
+        successful_transactions * U256::from(10_000u64) / total_transactions
+    };
+    
+    requirements
+        .iter()
+        .filter(|req| req.tier != Tier::Unverified)
+        .filter(|req| {
+            total_transactions >= req.min_transactions
+                && success_rate_bps >= req.min_success_rate_bps
+                && stake >= req.min_stake
+        })
+        .map(|req| req.tier)
+        .max_by_key(|tier| *tier as u8)
+        .unwrap_or(Tier::Unverified)
+}
+
+/// Assumed price premium, in basis points, a provider can charge at each
+/// tier (indexed by `Tier as usize`). Off-chain estimate used by `optimal_stake`.
+pub const TIER_PRICE_UPLIFT_BPS: [u64; 6] = [0, 0, 500, 1_000, 1_500, 2_000];
+
+/// Stake that maximizes monthly revenue uplift minus the cost of capital.
+///
+/// Candidates are the registry minimum and each tier's `min_stake`; the tier
+/// each candidate reaches is projected with the agent's transaction history.
+/// `capital_cost_bps` is an annual rate. Ties go to the smaller stake.
+pub fn optimal_stake_for(
+    total_transactions: U256,
+    successful_transactions: U256,
+    requirements: &[TierRequirement],
+    registry_min_stake: U256,
+    monthly_revenue: U256,
+    capital_cost_bps: u16,
+) -> U256 {
+    let denominator = U256::from(FEE_DENOMINATOR);
+    let profit = |stake: U256| -> I256 {
+        let tier = project_tier(total_transactions, successful_transactions, stake, requirements);
+        let uplift = monthly_revenue.saturating_mul(U256::from(TIER_PRICE_UPLIFT_BPS[tier as usize])) / denominator;
+        let capital_cost = stake.saturating_mul(U256::from(capital_cost_bps)) / denominator / U256::from(12);
+        I256::from_raw(uplift) - I256::from_raw(capital_cost)
+    };
+    
+    let mut candidates: Vec<U256> = requirements.iter()
+        .map(|req| req.min_stake.max(registry_min_stake))
+        .chain(std::iter::once(registry_min_stake))
+        .collect();
+    candidates.sort();
+    candidates.dedup();
+    
+    candidates
+        .into_iter()
+        .fold((registry_min_stake, profit(registry_min_stake)), |best, stake| {
+            let candidate = profit(stake);
+            if candidate > best.1 { (stake, candidate) } else { best }
+        })
+        .0
+}
+
+/// Annualized return, in percent, of the stake that keeps a provider at `tier`.
+///
+/// Of `revenue` earned over `elapsed_secs`, the share attributed to the tier
+/// is the extra `TIER_PRICE_UPLIFT_BPS` premium over the tier one level down.
+/// Returns 0 without a tier premium, stake or elapsed time.
+pub fn stake_yield_for(revenue: U256, elapsed_secs: u64, tier: Tier, tier_min_stake: U256) -> f64 {
+    let tier = tier as usize;
+    if tier == 0 || elapsed_secs == 0 || tier_min_stake.is_zero() {
+        return 0.0;
+    }
+    
+    let uplift = TIER_PRICE_UPLIFT_BPS[tier] as f64;
+    let marginal_bps = uplift - TIER_PRICE_UPLIFT_BPS[tier - 1] as f64;
+    let attributable = u256_to_f64(revenue) * marginal_bps / (FEE_DENOMINATOR as f64 + uplift);
+    let annualized = attributable * (365.0 * 24.0 * 3600.0) / elapsed_secs as f64;
+    
+    annualized * 100.0 / u256_to_f64(tier_min_stake)
+}
+
+/// Future requests over which `request_expected_value` charges the price
+/// premium lost to a tier drop
+pub const REQUEST_EV_HORIZON: u64 = 100;
+
+/// Expected value, in wei, of accepting a request paying `offered_amount`
+/// (net of protocol fee) that costs `estimated_cost` (gas included) to serve.
+///
+/// The payment is only earned on success. A failure that would drop the
+/// provider's tier also costs the `TIER_PRICE_UPLIFT_BPS` premium it loses
+/// on the next `REQUEST_EV_HORIZON` requests of the same size:
+/// `(1 - p) * offered - cost - p * reputation_cost`.
+pub fn request_expected_value(
+    offered_amount: U256,
+    estimated_cost: U256,
+    failure_probability: f64,
+    reputation_impact: &FailureImpact,
+) -> f64 {
+    let p = failure_probability.clamp(0.0, 1.0);
+    let offered = u256_to_f64(offered_amount);
+    
+    let lost_bps = TIER_PRICE_UPLIFT_BPS[reputation_impact.current_tier as usize]
+        .saturating_sub(TIER_PRICE_UPLIFT_BPS[reputation_impact.projected_tier as usize]);
+    let reputation_cost = offered * lost_bps as f64 / FEE_DENOMINATOR as f64 * REQUEST_EV_HORIZON as f64;
+    
+    (1.0 - p) * offered - u256_to_f64(estimated_cost) - p * reputation_cost
+}
+
+/// Projected effect of one more failed transaction on an agent's standing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailureImpact {
+    pub current_tier: Tier,
+    pub projected_tier: Tier,
+    /// Success rate in percent
+    pub current_success_rate: f64,
+    /// Success rate in percent after the failure
+    pub projected_success_rate: f64,
+    pub current_score: U256,
+    pub projected_score: U256,
+}
+
+impl FailureImpact {
+    /// Whether the failure would demote the agent
+    pub fn tier_drop(&self) -> bool {
+        (self.projected_tier as u8) < (self.current_tier as u8)
+    }
+}
+
 /// Pricing model for services
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum PricingModel {
@@ -194,6 +449,298 @@ impl From<u8> for ChannelStatus {
     }
 }
 
+/// Settlement layer of a chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChainKind {
+    L1,
+    L2,
+    Testnet,
+}
+
+/// Timing characteristics of a known chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainParams {
+    pub name: &'static str,
+    pub kind: ChainKind,
+    pub block_time: Duration,
+    /// Time after which a payment can be treated as settled
+    pub finality: Duration,
+    /// Suggested value for `with_confirmations`
+    pub confirmations: usize,
+}
+
+/// Classification of the configured chain, see `chain_profile_for`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChainProfile {
+    Known(ChainParams),
+    /// Unrecognized chain id; configure timeouts and confirmations manually
+    Unknown,
+}
+
+/// Built-in profile for well-known chain ids
+pub fn chain_profile_for(chain_id: u64) -> ChainProfile {
+    let params = |name, kind, block_ms, finality_secs, confirmations| ChainProfile::Known(ChainParams {
+        name,
+        kind,
+        block_time: Duration::from_millis(block_ms),
+        finality: Duration::from_secs(finality_secs),
+        confirmations,
+    });
+    
+    match chain_id {
+        1 => params("Ethereum", ChainKind::L1, 12_000, 768, 12),
+        10 => params("Optimism", ChainKind::L2, 2_000, 60, 5),
+        137 => params("Polygon PoS", ChainKind::L2, 2_000, 120, 30),
+        324 => params("zkSync Era", ChainKind::L2, 1_000, 60, 5),
+        8453 => params("Base", ChainKind::L2, 2_000, 60, 5),
+        42161 => params("Arbitrum One", ChainKind::L2, 250, 60, 20),
+        11155111 => params("Sepolia", ChainKind::Testnet, 12_000, 768, 3),
+        84532 => params("Base Sepolia", ChainKind::Testnet, 2_000, 60, 2),
+        421614 => params("Arbitrum Sepolia", ChainKind::Testnet, 250, 60, 2),
+        1337 | 31337 => params("Local devnet", ChainKind::Testnet, 1_000, 1, 1),
+        _ => ChainProfile::Unknown,
+    }
+}
+
+/// SYNX token amount, stored internally in wei (18 decimals).
+///
+/// Using this instead of a bare `U256` keeps whole-token and wei amounts from
+/// being mixed up. Client methods that send, sign or encode a SYNX amount
+/// take `impl Into<U256>` and so accept it; thresholds, quantities and
+/// estimates stay plain `U256`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Synx(U256);
+
+impl Synx {
+    /// Parse a human-readable SYNX amount such as `"10.5"`
+    pub fn from_synx(amount: &str) -> Result<Self> {
+        ethers::utils::parse_ether(amount)
+            .map(Synx)
+            .map_err(|e| SynapseError::ConfigError(e.to_string()))
+    }
+    
+    /// Wrap an amount already expressed in wei
+    pub fn from_wei(wei: U256) -> Self {
+        Synx(wei)
+    }
+    
+    /// Amount in wei
+    pub fn as_wei(&self) -> U256 {
+        self.0
+    }
+}
+
+impl From<Synx> for U256 {
+    fn from(value: Synx) -> Self {
+        value.0
+    }
+}
+
+impl std::fmt::Display for Synx {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let formatted = ethers::utils::format_ether(self.0);
+        let trimmed = if formatted.contains('.') {
+            formatted.trim_end_matches('0').trim_end_matches('.')
+        } else {
+            formatted.as_str()
+        };
+        write!(f, "{} SYNX", trimmed)
+    }
+}
+
+/// Typical gas used by `PaymentRouter.pay`
+pub const PAY_GAS: u64 = 85_000;
+
+/// Typical gas used by `ReputationRegistry.registerAgent`
+pub const REGISTER_AGENT_GAS: u64 = 250_000;
+
+/// Typical gas used by `PaymentChannel.openChannel`
+pub const CHANNEL_OPEN_GAS: u64 = 150_000;
+
+/// Typical gas used to close a channel cooperatively
+pub const CHANNEL_CLOSE_GAS: u64 = 120_000;
+
+/// Typical gas used by `PaymentRouter.createStream`
+pub const STREAM_CREATE_GAS: u64 = 180_000;
+
+/// Typical gas used by `PaymentRouter.withdrawFromStream`
+pub const STREAM_WITHDRAW_GAS: u64 = 70_000;
+
+/// Number of payments above which a fixed cost is cheaper than paying
+/// `per_payment_cost` on every payment (`u64::MAX` if never)
+pub fn breakeven_payments(fixed_cost: U256, per_payment_cost: U256) -> u64 {
+    if per_payment_cost.is_zero() {
+        return u64::MAX;
+    }
+    let count = fixed_cost / per_payment_cost;
+    if count >= U256::from(u64::MAX) {
+        u64::MAX
+    } else {
+        count.as_u64()
+    }
+}
+
+/// Denominator for protocol fees expressed in basis points
+pub const FEE_DENOMINATOR: u64 = 10_000;
+
+/// Protocol fee charged on `amount` at `fee_bps` (deducted from the amount sent)
+pub fn protocol_fee(amount: U256, fee_bps: U256) -> U256 {
+    amount * fee_bps / U256::from(FEE_DENOMINATOR)
+}
+
+/// Router fee after a tier discount, rounded the way `PaymentRouter` does:
+/// `fee = amount * bps / 10000; fee -= fee * discount / 10000`
+pub fn discounted_fee(amount: U256, fee_bps: U256, discount_bps: U256) -> U256 {
+    let fee = protocol_fee(amount, fee_bps);
+    fee - fee * discount_bps.min(U256::from(FEE_DENOMINATOR)) / U256::from(FEE_DENOMINATOR)
+}
+
+/// `discounted_fee` that returns `None` instead of overflowing
+fn checked_discounted_fee(amount: U256, fee_bps: U256, discount_bps: U256) -> Option<U256> {
+    let denominator = U256::from(FEE_DENOMINATOR);
+    let fee = amount.checked_mul(fee_bps)? / denominator;
+    Some(fee - fee.checked_mul(discount_bps.min(denominator))? / denominator)
+}
+
+/// Smallest gross amount whose payment leaves exactly `net` after
+/// `discounted_fee`, or `None` if the fee takes the whole amount or the
+/// gross amount does not fit in a `U256`
+pub fn gross_for_net(net: U256, fee_bps: U256, discount_bps: U256) -> Option<U256> {
+    let denominator = U256::from(FEE_DENOMINATOR);
+    if fee_bps >= denominator {
+        return None;
+    }
+    
+    // The undiscounted fee bounds the gross from above
+    let mut low = net;
+    let scaled = net.checked_mul(denominator)?.checked_add(denominator - fee_bps - 1)?;
+    let mut high = (scaled / (denominator - fee_bps)).checked_add(U256::one())?;
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if mid - checked_discounted_fee(mid, fee_bps, discount_bps)? >= net {
+            high = mid;
+        } else {
+            low = mid + 1;
+        }
+    }
+    
+    Some(low)
+}
+
+/// Slack, in wei, allowed between the requested and delivered net amount of `pay_exact_net`
+pub const NET_PAYMENT_TOLERANCE: u64 = 1;
+
+/// Lossy conversion of a `U256` to `f64`
+fn u256_to_f64(value: U256) -> f64 {
+    value.to_string().parse().unwrap_or(f64::MAX)
+}
+
+/// Calldata of a contract call built through abigen bindings, which always
+/// carry their encoded arguments
+fn encoded<M: Middleware, D: Detokenize>(call: ContractCall<M, D>) -> Bytes {
+    call.calldata().unwrap_or_default()
+}
+
+/// Churn risk of a provider in `[0, 1]` from its trend over a window.
+///
+/// Averages the relative drop in reputation score (`score_start` to
+/// `score_end`) and in request volume (first half of the window to the
+/// second half); increases count as zero. A provider with no requests in
+/// either half gets a volume drop of 0.5, since silence is itself a warning sign.
+pub fn churn_risk_score(score_start: U256, score_end: U256, volume_first_half: u64, volume_second_half: u64) -> f64 {
+    let reputation_drop = if score_start.is_zero() || score_end >= score_start {
+        0.0
+    } else {
+        u256_to_f64(score_start - score_end) / u256_to_f64(score_start)
+    };
+    
+    let volume_drop = match (volume_first_half, volume_second_half) {
+        (0, 0) => 0.5,
+        (0, _) => 0.0,
+        (first, second) => first.saturating_sub(second) as f64 / first as f64,
+    };
+    
+    ((reputation_drop + volume_drop) / 2.0).clamp(0.0, 1.0)
+}
+
+/// Ranking score of an agent: reputation score weighted by success rate
+pub fn agent_score(agent: &AgentInfo) -> f64 {
+    let reputation = u256_to_f64(agent.reputation_score);
+    let success = agent.success_rate / 100.0;
+    reputation * success
+}
+
+/// Stake-weighted average `agent_score` of a group of agents.
+///
+/// Falls back to the plain average when no agent has stake; an empty group scores 0.
+pub fn pool_score(agents: &[AgentInfo]) -> f64 {
+    if agents.is_empty() {
+        return 0.0;
+    }
+    
+    let total_stake: f64 = agents.iter().map(|agent| u256_to_f64(agent.stake)).sum();
+    if total_stake == 0.0 {
+        return agents.iter().map(agent_score).sum::<f64>() / agents.len() as f64;
+    }
+    
+    agents.iter()
+        .map(|agent| agent_score(agent) * u256_to_f64(agent.stake))
+        .sum::<f64>() / total_stake
+}
+
+/// Ranking score of a service: `agent_score` of its provider, discounted
+/// by the service's base price in SYNX
+pub fn service_score(provider: &AgentInfo, base_price: U256) -> f64 {
+    let price = u256_to_f64(base_price) / 1e18;
+    agent_score(provider) / (1.0 + price)
+}
+
+/// Multiply a wei amount by a float factor (3 decimal places of precision)
+fn scale_u256(value: U256, factor: f64) -> U256 {
+    value * U256::from((factor * 1000.0).round() as u64) / U256::from(1000u64)
+}
+
+/// EIP-3009 type string for `transferWithAuthorization`
+pub const TRANSFER_WITH_AUTHORIZATION_TYPE: &str =
+    "TransferWithAuthorization(address from,address to,uint256 value,uint256 validAfter,uint256 validBefore,bytes32 nonce)";
+
+/// Signed EIP-3009 transfer authorization, submittable by any relayer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedAuthorization {
+    pub from: Address,
+    pub to: Address,
+    pub value: U256,
+    pub valid_after: U256,
+    pub valid_before: U256,
+    pub nonce: [u8; 32],
+    pub v: u8,
+    pub r: [u8; 32],
+    pub s: [u8; 32],
+}
+
+/// Escrow status
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EscrowStatus {
+    Open = 0,
+    Released = 1,
+    Refunded = 2,
+    Disputed = 3,
+    /// Status code not known to this SDK
+    Unknown = 255,
+}
+
+impl From<u8> for EscrowStatus {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => EscrowStatus::Open,
+            1 => EscrowStatus::Released,
+            2 => EscrowStatus::Refunded,
+            3 => EscrowStatus::Disputed,
+            _ => EscrowStatus::Unknown,
+        }
+    }
+}
+
 /// Contract addresses configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ContractAddresses {
@@ -204,12 +751,91 @@ pub struct ContractAddresses {
     pub payment_channel: Address,
 }
 
+/// Transaction fee model of the target chain
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GasModel {
+    /// Pre-1559 chains: a single `gas_price`
+    Legacy,
+    /// EIP-1559 chains: `max_fee_per_gas` and `max_priority_fee_per_gas`
+    #[default]
+    Eip1559,
+}
+
+/// Default gateway used to resolve `ipfs://` metadata URIs
+pub const DEFAULT_IPFS_GATEWAY: &str = "https://ipfs.io/ipfs/";
+
+fn default_ipfs_gateway() -> String {
+    DEFAULT_IPFS_GATEWAY.to_string()
+}
+
+fn default_confirmations() -> usize {
+    1
+}
+
+fn default_gas_price_multiplier() -> f64 {
+    1.0
+}
+
+fn default_deployment_block() -> u64 {
+    0
+}
+
+fn default_batcher() -> Option<Address> {
+    None
+}
+
+fn default_view_retries() -> u32 {
+    2
+}
+
+fn default_view_cache_ttl() -> Option<Duration> {
+    None
+}
+
+fn default_payer() -> Option<Address> {
+    None
+}
+
+fn default_max_stream_backdate() -> Duration {
+    Duration::from_secs(300)
+}
+
 /// SDK configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub rpc_url: String,
     pub chain_id: u64,
     pub contracts: ContractAddresses,
+    /// HTTP gateway prefix for `ipfs://` URIs (e.g. `https://ipfs.io/ipfs/`)
+    #[serde(default = "default_ipfs_gateway")]
+    pub ipfs_gateway: String,
+    /// Confirmations to wait for after sending a transaction
+    #[serde(default = "default_confirmations")]
+    pub confirmations: usize,
+    /// Factor applied to estimated EIP-1559 fees before sending (>= 1.0)
+    #[serde(default = "default_gas_price_multiplier")]
+    pub gas_price_multiplier: f64,
+    /// Block the protocol contracts were deployed at; event scans start here
+    #[serde(default = "default_deployment_block")]
+    pub deployment_block: u64,
+    /// Trusted forwarder that executes several protocol calls in one transaction
+    #[serde(default = "default_batcher")]
+    pub batcher: Option<Address>,
+    /// Retries for failed view calls (reverts are never retried)
+    #[serde(default = "default_view_retries")]
+    pub view_retries: u32,
+    /// How long view call results are cached; `None` disables caching
+    #[serde(default = "default_view_cache_ttl")]
+    pub view_cache_ttl: Option<Duration>,
+    /// Treasury that funds payments made by this agent (see `with_payer`)
+    #[serde(default = "default_payer")]
+    pub payer: Option<Address>,
+    /// Fee model used when building transactions
+    #[serde(default)]
+    pub gas_model: GasModel,
+    /// How far before the latest block a new stream may start
+    #[serde(default = "default_max_stream_backdate")]
+    pub max_stream_backdate: Duration,
 }
 
 /// Agent information
@@ -227,6 +853,375 @@ pub struct AgentInfo {
     pub success_rate: f64,
 }
 
+/// A single planned operation that pulls SYNX from the caller
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PlannedSpend {
+    Payment(U256),
+    Escrow(U256),
+    Stream(U256),
+    Stake(U256),
+    ChannelDeposit(U256),
+}
+
+/// Planned spend for a session, used to size bounded approvals
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SpendPlan {
+    pub operations: Vec<PlannedSpend>,
+    /// Extra allowance on top of the planned total, in basis points
+    pub margin_bps: u16,
+}
+
+impl SpendPlan {
+    /// Sum the planned spend per spender contract, including the margin
+    pub fn allowances(&self, contracts: &ContractAddresses) -> Vec<(Address, U256)> {
+        let mut totals: std::collections::BTreeMap<Address, U256> = std::collections::BTreeMap::new();
+        
+        for op in &self.operations {
+            let (spender, amount) = match *op {
+                PlannedSpend::Payment(a) | PlannedSpend::Escrow(a) | PlannedSpend::Stream(a) => {
+                    (contracts.payment_router, a)
+                }
+                PlannedSpend::Stake(a) => (contracts.reputation, a),
+                PlannedSpend::ChannelDeposit(a) => (contracts.payment_channel, a),
+            };
+            let total = totals.entry(spender).or_insert_with(U256::zero);
+            *total = total.saturating_add(amount);
+        }
+        
+        totals
+            .into_iter()
+            .map(|(spender, total)| {
+                let margin = total * U256::from(self.margin_bps) / U256::from(10_000u64);
+                (spender, total.saturating_add(margin))
+            })
+            .collect()
+    }
+}
+
+/// What an agent uses the protocol for, which decides the contracts it must approve
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AgentRole {
+    /// Sends payments, escrows and streams through the router
+    Payer,
+    /// Registers as an agent and lists services
+    Provider,
+    /// Opens and funds payment channels
+    ChannelUser,
+    /// Only manages reputation stake
+    Staker,
+}
+
+impl AgentRole {
+    /// Minimal set of spender contracts this role needs an allowance for
+    pub fn spenders(&self, contracts: &ContractAddresses) -> Vec<Address> {
+        match self {
+            AgentRole::Payer => vec![contracts.payment_router],
+            AgentRole::Provider => vec![contracts.reputation, contracts.service_registry],
+            AgentRole::ChannelUser => vec![contracts.payment_channel],
+            AgentRole::Staker => vec![contracts.reputation],
+        }
+    }
+}
+
+/// Off-chain agent metadata document referenced by `AgentInfo.metadata_uri`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentMetadata {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub image: Option<String>,
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+    #[serde(default)]
+    pub endpoints: Vec<String>,
+    /// Any fields not covered above
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Hash of an agent metadata document: `keccak256` of its canonical JSON,
+/// i.e. compact output with object keys sorted at every level.
+///
+/// Floats are written as serde_json formats them; publishers should avoid
+/// them in fields that must hash identically across implementations.
+pub fn metadata_document_hash(doc: &AgentMetadata) -> [u8; 32] {
+    // `serde_json::Value` objects are key-ordered maps
+    let canonical = serde_json::to_value(doc)
+        .and_then(|value| serde_json::to_vec(&value))
+        .unwrap_or_default();
+    ethers::utils::keccak256(canonical)
+}
+
+/// Append the document hash to a metadata URI as `#keccak256=0x<hash>`, so
+/// the hash is registered on-chain together with the URI
+pub fn metadata_uri_with_hash(uri: &str, doc: &AgentMetadata) -> String {
+    let base = uri.split('#').next().unwrap_or(uri);
+    format!("{}#keccak256=0x{}", base, hex::encode(metadata_document_hash(doc)))
+}
+
+/// Document hash carried by a metadata URI, if it has a `#keccak256=` fragment
+pub fn metadata_uri_hash(uri: &str) -> Option<[u8; 32]> {
+    let (_, fragment) = uri.split_once('#')?;
+    let hash = fragment.strip_prefix("keccak256=")?;
+    let bytes = hex::decode(hash.trim_start_matches("0x")).ok()?;
+    bytes.try_into().ok()
+}
+
+/// Resolve a metadata URI to a fetchable HTTPS URL.
+///
+/// `ipfs://<cid>/<path>` is rewritten onto `gateway`; `https://` URIs are
+/// returned unchanged. Any other scheme is rejected.
+pub fn resolve_metadata_uri(uri: &str, gateway: &str) -> Result<String> {
+    if let Some(path) = uri.strip_prefix("ipfs://") {
+        let path = path.strip_prefix("ipfs/").unwrap_or(path);
+        Ok(format!("{}/{}", gateway.trim_end_matches('/'), path))
+    } else if uri.starts_with("https://") {
+        Ok(uri.to_string())
+    } else {
+        Err(SynapseError::MetadataError(format!("Unsupported metadata URI: {}", uri)))
+    }
+}
+
+/// Upfront cost of registering as an agent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegistrationCost {
+    /// Stake that will be locked
+    pub stake: U256,
+    /// Minimum stake accepted by the registry
+    pub min_stake: U256,
+    /// Protocol registration fee in SYNX
+    pub registration_fee: U256,
+    /// Total SYNX needed: stake + registration fee
+    pub total_synx: U256,
+    pub gas_estimate: U256,
+    pub gas_price: U256,
+    /// Native token needed for gas
+    pub gas_cost: U256,
+}
+
+impl RegistrationCost {
+    /// Whether the planned stake satisfies the minimum
+    pub fn meets_min_stake(&self) -> bool {
+        self.stake >= self.min_stake
+    }
+}
+
+/// Everything relevant about this agent in one serializable dump
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiagnosticSnapshot {
+    pub address: Address,
+    pub chain_id: u64,
+    pub block_number: u64,
+    pub synx_balance: U256,
+    pub native_balance: U256,
+    pub agent: AgentInfo,
+    /// Allowance to each protocol contract
+    pub allowances: Vec<(Address, U256)>,
+    /// Nonce of the next transaction per the latest block
+    pub confirmed_nonce: U256,
+    /// Nonce including transactions still in the mempool
+    pub pending_nonce: U256,
+}
+
+impl DiagnosticSnapshot {
+    /// Number of this wallet's transactions still waiting to be mined
+    pub fn pending_transactions(&self) -> U256 {
+        self.pending_nonce.saturating_sub(self.confirmed_nonce)
+    }
+}
+
+/// Operational summary of this agent's activity since a block, from `agent_metrics`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentMetrics {
+    pub from_block: u64,
+    pub to_block: u64,
+    /// Gross amount of payments sent, fees included
+    pub total_paid: U256,
+    /// Amount of payments received, net of fees
+    pub total_received: U256,
+    /// Protocol fees on payments sent
+    pub total_fees: U256,
+    pub payments_sent: usize,
+    pub payments_received: usize,
+    /// Channels opened since `from_block` that are open or closing
+    pub open_channels: usize,
+    /// Streams sent or received since `from_block` that are still active
+    pub active_streams: usize,
+    pub agent: AgentInfo,
+}
+
+/// Allowance, in whole SYNX, below which `approval_remaining` flags that
+/// a protocol contract needs a top-up
+pub const ALLOWANCE_TOPUP_THRESHOLD_SYNX: u64 = 1_000;
+
+/// Remaining allowance to one protocol contract, from `approval_remaining`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AllowanceStatus {
+    pub spender: Address,
+    pub allowance: U256,
+    /// Below `ALLOWANCE_TOPUP_THRESHOLD_SYNX`; re-approve before it runs out
+    pub needs_topup: bool,
+}
+
+/// Blocks of history `audit_approvals` uses to measure recent spending
+pub const APPROVAL_AUDIT_WINDOW: u64 = 50_000;
+
+/// Allowance above this multiple of recent usage is considered excessive
+pub const APPROVAL_EXCESS_FACTOR: u64 = 10;
+
+/// Outstanding allowance to one protocol contract, as judged by `audit_approvals`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApprovalAudit {
+    pub spender: Address,
+    pub allowance: U256,
+    /// SYNX pulled by the spender over the audit window, if it can be measured
+    pub recent_usage: Option<U256>,
+    pub recommend_revocation: bool,
+}
+
+/// Resource-usage metadata attached to a payment for sustainability reporting.
+///
+/// Encoded as UTF-8 JSON, e.g. `{"energyWh":1.5,"durationMs":820,"model":"llama-3-8b"}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComputeReceipt {
+    pub energy_wh: f64,
+    pub duration_ms: u64,
+    pub model: String,
+}
+
+impl ComputeReceipt {
+    /// Encode as payment metadata
+    pub fn encode(&self) -> Bytes {
+        serde_json::to_vec(self).unwrap_or_default().into()
+    }
+    
+    /// Decode payment metadata
+    pub fn decode(metadata: &[u8]) -> Result<Self> {
+        serde_json::from_slice(metadata).map_err(|e| SynapseError::MetadataError(e.to_string()))
+    }
+}
+
+/// Compute usage aggregated over payments by `sum_compute`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ComputeSummary {
+    /// Payments that carried a `ComputeReceipt`
+    pub payments: usize,
+    pub total_energy_wh: f64,
+    pub total_duration_ms: u64,
+    /// Energy per model
+    pub energy_by_model: std::collections::BTreeMap<String, f64>,
+    /// Requested payments not found or without compute metadata
+    pub missing: Vec<[u8; 32]>,
+}
+
+/// Payment metadata of `pay_with_tip`, separating the quoted price from
+/// the voluntary tip so revenue accounting can tell them apart.
+///
+/// Encoded as UTF-8 JSON; `data` carries the caller's own metadata.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TipMetadata {
+    pub service_id: H256,
+    pub price: U256,
+    pub tip: U256,
+    pub data: Bytes,
+}
+
+impl TipMetadata {
+    /// Encode as payment metadata
+    pub fn encode(&self) -> Bytes {
+        serde_json::to_vec(self).unwrap_or_default().into()
+    }
+    
+    /// Decode payment metadata
+    pub fn decode(metadata: &[u8]) -> Result<Self> {
+        serde_json::from_slice(metadata).map_err(|e| SynapseError::MetadataError(e.to_string()))
+    }
+}
+
+/// Deepest expansion `trust_graph` performs from its seeds
+pub const TRUST_GRAPH_MAX_DEPTH: u8 = 3;
+
+/// Agents whose outgoing payments `trust_graph` scans before it stops expanding
+pub const TRUST_GRAPH_MAX_AGENTS: usize = 200;
+
+/// Aggregated payments from one agent to another
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrustEdge {
+    pub from: Address,
+    pub to: Address,
+    /// Total amount paid
+    pub volume: U256,
+    pub count: u64,
+}
+
+/// Directed who-pays-whom graph built by `trust_graph`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrustGraph {
+    /// Every agent seen, seeds first, in discovery order
+    pub nodes: Vec<Address>,
+    pub edges: Vec<TrustEdge>,
+}
+
+impl TrustGraph {
+    /// PageRank over the graph with edges weighted by volume.
+    ///
+    /// Rank flows from payer to payee, so agents paid by well-ranked agents
+    /// rank higher. Agents that pay no one spread their rank evenly.
+    pub fn page_rank(&self, damping: f64, iterations: usize) -> HashMap<Address, f64> {
+        let n = self.nodes.len();
+        if n == 0 {
+            return HashMap::new();
+        }
+        
+        let index: HashMap<Address, usize> = self.nodes.iter().enumerate().map(|(i, a)| (*a, i)).collect();
+        let mut out_volume = vec![0.0; n];
+        for edge in &self.edges {
+            out_volume[index[&edge.from]] += u256_to_f64(edge.volume);
+        }
+        
+        let mut rank = vec![1.0 / n as f64; n];
+        for _ in 0..iterations {
+            let dangling: f64 = (0..n).filter(|i| out_volume[*i] == 0.0).map(|i| rank[i]).sum();
+            let mut next = vec![(1.0 - damping) / n as f64 + damping * dangling / n as f64; n];
+            for edge in &self.edges {
+                let (from, to) = (index[&edge.from], index[&edge.to]);
+                next[to] += damping * rank[from] * u256_to_f64(edge.volume) / out_volume[from];
+            }
+            rank = next;
+        }
+        
+        self.nodes.iter().copied().zip(rank).collect()
+    }
+}
+
+/// Blocks of payment history `runway_balance` samples
+pub const RUNWAY_SAMPLE_BLOCKS: u64 = 50_000;
+
+/// Most recent payments whose receipts `runway_balance` inspects for gas
+const RUNWAY_SAMPLE_RECEIPTS: usize = 20;
+
+/// Funding an agent needs for a planned period, from `runway_balance`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunwayEstimate {
+    pub transactions: u64,
+    /// Average SYNX sent per payment, fee included
+    pub synx_per_tx: U256,
+    /// Average native gas cost per payment
+    pub gas_cost_per_tx: U256,
+    pub synx_needed: U256,
+    pub native_needed: U256,
+    /// Payments the averages were measured from; 0 means gas fell back to
+    /// `PAY_GAS` at the current gas price and no SYNX usage was known
+    pub sampled_payments: usize,
+}
+
 /// Service information
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServiceInfo {
@@ -243,6 +1238,25 @@ pub struct ServiceInfo {
     pub created_at: U256,
 }
 
+/// Cheapest service found by `find_cheapest_total`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheapestOffer {
+    pub service_id: [u8; 32],
+    /// Gross SYNX payment, protocol fee included, in SYNX wei
+    pub synx_total: U256,
+    /// Gas cost of the payment in native-token wei
+    pub gas_wei: U256,
+}
+
+/// Outcome of `pause_all_services`
+#[derive(Debug)]
+pub struct PauseReport {
+    /// `(service_id, result)` per deactivation sent
+    pub outcomes: Vec<([u8; 32], Result<H256>)>,
+    /// Every service now remembered as paused, including earlier pauses
+    pub paused: Vec<[u8; 32]>,
+}
+
 /// Channel information
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChannelInfo {
@@ -260,10 +1274,30 @@ pub struct ChannelInfo {
 pub struct PaymentResult {
     pub tx_hash: H256,
     pub payment_id: H256,
+    pub recipient: Address,
     pub amount: U256,
     pub fee: U256,
 }
 
+/// On-chain status of a sent payment, from `verify_payments`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PaymentStatus {
+    /// Mined with a `Payment` event matching id, recipient and amount
+    Confirmed,
+    /// Mined but reverted
+    Reverted,
+    /// No receipt yet, or no matching `Payment` event in it
+    NotFound,
+}
+
+/// Verification outcome of one `PaymentResult`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PaymentVerification {
+    pub payment_id: H256,
+    pub tx_hash: H256,
+    pub status: PaymentStatus,
+}
+
 /// Stream result
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StreamResult {
@@ -274,479 +1308,5820 @@ pub struct StreamResult {
     pub end_time: U256,
 }
 
-/// SYNAPSE Protocol Client
-pub struct SynapseClient<M: Middleware> {
-    provider: Arc<M>,
-    wallet: LocalWallet,
-    config: Config,
-    token: SynapseToken<M>,
-    router: PaymentRouter<M>,
-    reputation: ReputationRegistry<M>,
-    services: ServiceRegistry<M>,
-    channels: PaymentChannel<M>,
+/// Heuristic MEV exposure of a batch payout; advisory only, a clean report
+/// is not a guarantee
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MevReport {
+    /// Recipients that respond like AMM pools; paying them moves pool
+    /// balances and can be sandwiched
+    pub amm_recipients: Vec<Address>,
+    /// Recipients with contract code, whose behaviour may depend on ordering
+    pub contract_recipients: Vec<Address>,
+    /// Recipients paid more than once in the batch
+    pub duplicate_recipients: Vec<Address>,
+    /// Total sent to `amm_recipients`
+    pub amm_exposure: U256,
 }
 
-impl SynapseClient<SignerMiddleware<Provider<Http>, LocalWallet>> {
-    /// Create a new client
-    pub async fn new(
-        rpc_url: &str,
-        private_key: &str,
-        contracts: ContractAddresses,
-    ) -> Result<Self> {
-        let provider = Provider::<Http>::try_from(rpc_url)
-            .map_err(|e| SynapseError::ConfigError(e.to_string()))?;
-        
-        let chain_id = provider.get_chainid().await?;
-        
-        let wallet: LocalWallet = private_key
-            .parse::<LocalWallet>()
-            .map_err(|e| SynapseError::ConfigError(e.to_string()))?
-            .with_chain_id(chain_id.as_u64());
-        
-        let client = SignerMiddleware::new(provider, wallet.clone());
-        let client = Arc::new(client);
-        
-        let token = SynapseToken::new(contracts.token, client.clone());
-        let router = PaymentRouter::new(contracts.payment_router, client.clone());
-        let reputation = ReputationRegistry::new(contracts.reputation, client.clone());
-        let services = ServiceRegistry::new(contracts.service_registry, client.clone());
-        let channels = PaymentChannel::new(contracts.payment_channel, client.clone());
-        
-        let config = Config {
-            rpc_url: rpc_url.to_string(),
-            chain_id: chain_id.as_u64(),
-            contracts,
+impl MevReport {
+    pub fn is_clean(&self) -> bool {
+        self.amm_recipients.is_empty() && self.duplicate_recipients.is_empty()
+    }
+}
+
+/// A router payment to be signed offline by `sign_payments_offline`.
+///
+/// Gas values must be supplied because an air-gapped signer cannot query
+/// the network; with `GasModel::Legacy`, `max_fee_per_gas` is used as the gas price.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OfflinePayment {
+    pub recipient: Address,
+    pub amount: U256,
+    pub metadata: Bytes,
+    pub gas_limit: U256,
+    pub max_fee_per_gas: U256,
+    pub max_priority_fee_per_gas: U256,
+}
+
+/// Signed, not yet broadcast transaction produced by `sign_payments_offline`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedTransaction {
+    pub nonce: U256,
+    pub payment_id: H256,
+    pub tx_hash: H256,
+    /// RLP-encoded signed transaction for `eth_sendRawTransaction`
+    pub raw: Bytes,
+}
+
+/// Reverted transaction with the context needed to send it again, from
+/// `failed_tx`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FailedTx {
+    pub tx_hash: H256,
+    pub to: Address,
+    pub data: Bytes,
+    pub value: U256,
+    pub gas_limit: U256,
+    pub gas_used: U256,
+}
+
+impl FailedTx {
+    /// True if the transaction used its whole gas limit, i.e. most likely
+    /// reverted out of gas
+    pub fn out_of_gas(&self) -> bool {
+        self.gas_used >= self.gas_limit
+    }
+}
+
+/// Changes applied by `retry_with`; `None` keeps the original value
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TxAdjustments {
+    /// Gas limit; when `None` the limit is estimated again
+    pub gas_limit: Option<U256>,
+    /// Replacement calldata, e.g. the same call with updated arguments
+    pub data: Option<Bytes>,
+    pub value: Option<U256>,
+}
+
+/// Price or availability change of a service between two blocks
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServiceChange {
+    pub service_id: [u8; 32],
+    pub old_price: U256,
+    pub new_price: U256,
+    pub was_active: bool,
+    pub is_active: bool,
+}
+
+/// Differences in a category's services between two blocks
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CategoryDiff {
+    pub added: Vec<[u8; 32]>,
+    pub removed: Vec<[u8; 32]>,
+    pub modified: Vec<ServiceChange>,
+}
+
+impl CategoryDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+/// Way of settling a payment relationship
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PaymentMethod {
+    Direct,
+    Channel,
+    Stream,
+}
+
+/// Output of `recommend_payment_method`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PaymentMethodRecommendation {
+    pub counterparty: Address,
+    pub method: PaymentMethod,
+    /// Average amount per payment
+    pub per_payment: U256,
+    /// Gas the recommended method is expected to use in total
+    pub estimated_gas: u64,
+}
+
+/// Parameters of one stream in `create_streams`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamSpec {
+    pub recipient: Address,
+    pub total_amount: U256,
+    pub start_time: U256,
+    pub end_time: U256,
+}
+
+/// Protocol operation that can be bundled by `execute_batch`
+#[derive(Debug, Clone)]
+pub enum SynapseCall {
+    Pay {
+        recipient: Address,
+        amount: U256,
+        metadata: Bytes,
+    },
+    CreateEscrow {
+        escrow_id: [u8; 32],
+        recipient: Address,
+        arbiter: Address,
+        amount: U256,
+        deadline: U256,
+    },
+    ReleaseEscrow {
+        escrow_id: [u8; 32],
+    },
+    IncreaseStake {
+        amount: U256,
+    },
+    UpdateService {
+        service_id: [u8; 32],
+        description: String,
+        endpoint: String,
+        base_price: U256,
+    },
+}
+
+/// Escrow information
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EscrowInfo {
+    pub escrow_id: H256,
+    pub sender: Address,
+    pub recipient: Address,
+    pub arbiter: Address,
+    pub amount: U256,
+    pub deadline: U256,
+    pub status: EscrowStatus,
+}
+
+/// Escrow on an arbiter's worklist, from `arbiter_dashboard`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArbiterEscrow {
+    pub escrow: EscrowInfo,
+    /// Chain time left until the deadline; zero once it has passed
+    pub time_to_deadline: Duration,
+}
+
+/// Metadata attached to `event`, decoded from the `pay` / `batchPay`
+/// calldata of the transaction that carried it
+fn payment_metadata(event: &PaymentEvent, calldata: &[u8]) -> Option<Bytes> {
+    use ethers::abi::AbiDecode;
+    
+    match PaymentRouterCalls::decode(calldata).ok()? {
+        PaymentRouterCalls::Pay(call) => Some(call.metadata),
+        PaymentRouterCalls::BatchPay(call) => call.payment_ids.iter()
+            .position(|id| *id == event.payment_id.0)
+            .and_then(|i| call.metadata.get(i).cloned()),
+        _ => None,
+    }
+}
+
+/// Blocks of service history `recommend_escrow_deadline` samples
+pub const ESCROW_HISTORY_BLOCKS: u64 = 50_000;
+
+/// Most recent service requests `recommend_escrow_deadline` measures
+const ESCROW_SAMPLE_REQUESTS: usize = 50;
+
+/// Multiple of the observed 90th-percentile fulfillment time a recommended
+/// escrow deadline allows
+pub const ESCROW_DEADLINE_MARGIN: u64 = 2;
+
+/// Seconds an escrow for a service should stay open.
+///
+/// With history this is `ESCROW_DEADLINE_MARGIN` times the 90th percentile
+/// of `fulfillment_secs`, but never shorter than one hour. Without history
+/// it falls back to a per-model default: one hour for per-request and
+/// per-token services, six hours per-byte, one day per-second and custom,
+/// thirty days for subscriptions.
+pub fn escrow_deadline_window(pricing_model: PricingModel, fulfillment_secs: &[u64]) -> u64 {
+    const HOUR: u64 = 3600;
+    
+    if fulfillment_secs.is_empty() {
+        return match pricing_model {
+            PricingModel::PerRequest | PricingModel::PerToken => HOUR,
+            PricingModel::PerByte => 6 * HOUR,
+            PricingModel::PerSecond | PricingModel::Custom => 24 * HOUR,
+            PricingModel::Subscription => 30 * 24 * HOUR,
         };
-        
-        Ok(Self {
-            provider: client,
-            wallet,
-            config,
-            token,
-            router,
-            reputation,
-            services,
-            channels,
-        })
     }
     
-    /// Get the client's address
-    pub fn address(&self) -> Address {
-        self.wallet.address()
+    let mut sorted = fulfillment_secs.to_vec();
+    sorted.sort_unstable();
+    let p90 = sorted[(sorted.len() * 9 / 10).min(sorted.len() - 1)];
+    p90.saturating_mul(ESCROW_DEADLINE_MARGIN).max(HOUR)
+}
+
+/// Blocks of price history `quote_validity` samples
+pub const QUOTE_HISTORY_BLOCKS: u64 = 100_000;
+
+/// Longest window `quote_validity_window` returns, also used without history
+pub const QUOTE_VALIDITY_MAX: Duration = Duration::from_secs(24 * 3600);
+
+/// How long a quoted price is likely to hold given the timestamps of a
+/// service's past price changes, oldest first.
+///
+/// Half the median interval between changes, capped at
+/// `QUOTE_VALIDITY_MAX`; fewer than two changes give the cap.
+pub fn quote_validity_window(change_times: &[u64]) -> Duration {
+    let mut intervals: Vec<u64> = change_times.windows(2)
+        .map(|pair| pair[1].saturating_sub(pair[0]))
+        .collect();
+    if intervals.is_empty() {
+        return QUOTE_VALIDITY_MAX;
     }
     
-    /// Get chain ID
-    pub fn chain_id(&self) -> u64 {
-        self.config.chain_id
+    intervals.sort_unstable();
+    let median = intervals[intervals.len() / 2];
+    Duration::from_secs(median / 2).min(QUOTE_VALIDITY_MAX)
+}
+
+/// Stream information
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamInfo {
+    pub sender: Address,
+    pub recipient: Address,
+    pub total_amount: U256,
+    pub withdrawn: U256,
+    pub start_time: U256,
+    pub end_time: U256,
+    pub active: bool,
+}
+
+/// Earliest timestamp at which a linear stream has accrued `target`, matching
+/// the router's `totalAmount * elapsed / duration` (rounded down) vesting.
+///
+/// Returns `None` if `target` exceeds the stream total or the schedule is empty.
+pub fn stream_vesting_time(total_amount: U256, start_time: U256, end_time: U256, target: U256) -> Option<U256> {
+    if target > total_amount || end_time <= start_time || total_amount.is_zero() {
+        return None;
     }
     
-    // ==================== Token Functions ====================
+    let duration = end_time - start_time;
+    let numerator = target.checked_mul(duration)?;
+    let elapsed = (numerator + total_amount - 1) / total_amount;
+    Some(start_time + elapsed)
+}
+
+/// Amount a linear stream has accrued by `time`, matching the router's
+/// `totalAmount * elapsed / duration` (withdrawals not subtracted)
+pub fn stream_accrued_at(total_amount: U256, start_time: U256, end_time: U256, time: U256) -> U256 {
+    if time <= start_time || end_time <= start_time {
+        return U256::zero();
+    }
     
-    /// Get token balance
-    pub async fn get_balance(&self, address: Address) -> Result<U256> {
-        let balance = self.token.balance_of(address).call().await
-            .map_err(|e| SynapseError::ContractError(e.to_string()))?;
-        Ok(balance)
+    let elapsed = time.min(end_time) - start_time;
+    total_amount.saturating_mul(elapsed) / (end_time - start_time)
+}
+
+/// Unused share of `paid_amount` when a subscription covering
+/// `[period_start, period_end]` is cancelled at `cancel_time`, rounded down.
+///
+/// Cancelling at or before the start refunds everything; at or after the
+/// end refunds nothing.
+pub fn prorated_refund(paid_amount: U256, period_start: U256, period_end: U256, cancel_time: U256) -> U256 {
+    if period_end <= period_start {
+        return U256::zero();
     }
     
-    /// Get own balance
-    pub async fn balance(&self) -> Result<U256> {
-        self.get_balance(self.address()).await
+    let cancel_time = cancel_time.max(period_start).min(period_end);
+    paid_amount.saturating_mul(period_end - cancel_time) / (period_end - period_start)
+}
+
+/// Timestamps above this are treated as milliseconds passed by mistake
+const MAX_PLAUSIBLE_TIMESTAMP: u64 = 100_000_000_000;
+
+/// Reject degenerate stream periods: an end not after the start, a start
+/// more than `max_backdate` before `now`, or timestamps in milliseconds
+pub fn validate_stream_times(start_time: U256, end_time: U256, now: U256, max_backdate: Duration) -> Result<()> {
+    let plausible = U256::from(MAX_PLAUSIBLE_TIMESTAMP);
+    if start_time > plausible || end_time > plausible {
+        return Err(SynapseError::InvalidInput(format!(
+            "Stream times {}..{} look like milliseconds; use Unix seconds",
+            start_time, end_time
+        )));
     }
     
-    /// Transfer tokens
-    pub async fn transfer(&self, to: Address, amount: U256) -> Result<H256> {
-        let tx = self.token.transfer(to, amount).send().await
-            .map_err(|e| SynapseError::ContractError(e.to_string()))?;
-        
-        let receipt = tx.await
-            .map_err(|e| SynapseError::TransactionFailed(e.to_string()))?
-            .ok_or(SynapseError::TransactionFailed("No receipt".to_string()))?;
-        
-        Ok(receipt.transaction_hash)
+    if end_time <= start_time {
+        return Err(SynapseError::InvalidInput(format!(
+            "Stream end {} must be after start {}",
+            end_time, start_time
+        )));
     }
     
-    /// Approve token spending
-    pub async fn approve(&self, spender: Address, amount: U256) -> Result<H256> {
-        let tx = self.token.approve(spender, amount).send().await
-            .map_err(|e| SynapseError::ContractError(e.to_string()))?;
-        
-        let receipt = tx.await
-            .map_err(|e| SynapseError::TransactionFailed(e.to_string()))?
-            .ok_or(SynapseError::TransactionFailed("No receipt".to_string()))?;
+    let earliest = now.saturating_sub(U256::from(max_backdate.as_secs()));
+    if start_time < earliest {
+        return Err(SynapseError::InvalidInput(format!(
+            "Stream start {} is more than {}s before the current block time {}",
+            start_time,
+            max_backdate.as_secs(),
+            now
+        )));
+    }
+    
+    Ok(())
+}
+
+/// Multiple of the measured response time `challenge_buffer` keeps in reserve
+pub const CHALLENGE_BUFFER_FACTOR: u32 = 2;
+
+/// Shortest lead time `challenge_buffer` recommends
+pub const CHALLENGE_BUFFER_MIN: Duration = Duration::from_secs(60);
+
+/// Lead time to keep before a challenge deadline: the time to get a
+/// transaction in and confirmed (`confirmations + 1` blocks plus one RPC
+/// round trip), times `CHALLENGE_BUFFER_FACTOR`, at least `CHALLENGE_BUFFER_MIN`
+pub fn challenge_buffer(block_time: Duration, confirmations: usize, rpc_latency: Duration) -> Duration {
+    let response = block_time * (confirmations as u32 + 1) + rpc_latency;
+    (response * CHALLENGE_BUFFER_FACTOR).max(CHALLENGE_BUFFER_MIN)
+}
+
+/// Check that a channel deposit covers the planned outgoing payments,
+/// failing with `InsufficientBalance` before the channel is opened
+pub fn validate_channel_capacity(my_deposit: U256, expected_payments: &[U256]) -> Result<()> {
+    let required = expected_payments.iter()
+        .fold(U256::zero(), |total, amount| total.saturating_add(*amount));
+    
+    if required > my_deposit {
+        return Err(SynapseError::InsufficientBalance { required, available: my_deposit });
+    }
+    
+    Ok(())
+}
+
+/// Split `total` in proportion to `weights`, rounding each share down and
+/// handing the leftover units out one each from the first share onward,
+/// so the shares always sum to `total`.
+pub fn split_by_weights(total: U256, weights: &[u32]) -> Result<Vec<U256>> {
+    if weights.is_empty() || weights.contains(&0) {
+        return Err(SynapseError::InvalidInput("Weights must be non-empty and positive".into()));
+    }
+    
+    let weight_sum: U256 = weights.iter().map(|w| U256::from(*w)).fold(U256::zero(), |a, b| a + b);
+    let mut shares: Vec<U256> = weights.iter()
+        .map(|w| total.full_mul(U256::from(*w)) / weight_sum)
+        .map(|share| U256::try_from(share).unwrap_or(U256::MAX))
+        .collect();
+    
+    let assigned = shares.iter().fold(U256::zero(), |a, b| a + *b);
+    let remainder = (total - assigned).as_usize();
+    for share in shares.iter_mut().take(remainder) {
+        *share += U256::one();
+    }
+    
+    Ok(shares)
+}
+
+/// Hash a stream recipient signs to let `delegate` withdraw on its behalf:
+/// `keccak256(abi.encode("SYNAPSE_STREAM_WITHDRAWAL", chainId, router, streamId, recipient, delegate, maxAmount))`
+pub fn stream_withdrawal_authorization_hash(
+    chain_id: u64,
+    router: Address,
+    stream_id: [u8; 32],
+    recipient: Address,
+    delegate: Address,
+    max_amount: U256,
+) -> [u8; 32] {
+    use ethers::abi::{encode, Token};
+    use ethers::utils::keccak256;
+    
+    keccak256(encode(&[
+        Token::String("SYNAPSE_STREAM_WITHDRAWAL".to_string()),
+        Token::Uint(U256::from(chain_id)),
+        Token::Address(router),
+        Token::FixedBytes(stream_id.to_vec()),
+        Token::Address(recipient),
+        Token::Address(delegate),
+        Token::Uint(max_amount),
+    ]))
+}
+
+/// Chain and `PaymentChannel` contract a channel state is bound to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ChannelDomain {
+    pub chain_id: u64,
+    pub contract: Address,
+}
+
+/// Hash of a channel state as packed by `PaymentChannel._hashState`:
+/// `keccak256(abi.encodePacked(channelId, balance1, balance2, nonce, chainid, channelContract))`.
+/// Participants sign its EIP-191 form; see `channel_state_digest`.
+pub fn channel_state_hash(
+    domain: ChannelDomain,
+    channel_id: [u8; 32],
+    balance1: U256,
+    balance2: U256,
+    nonce: U256,
+) -> [u8; 32] {
+    use ethers::utils::keccak256;
+    
+    let mut data = Vec::with_capacity(180);
+    let mut word = [0u8; 32];
+    data.extend_from_slice(&channel_id);
+    for value in [balance1, balance2, nonce, U256::from(domain.chain_id)] {
+        value.to_big_endian(&mut word);
+        data.extend_from_slice(&word);
+    }
+    data.extend_from_slice(domain.contract.as_bytes());
+    
+    keccak256(&data)
+}
+
+/// Hash `PaymentChannel.cooperativeClose` verifies:
+/// `keccak256(abi.encodePacked(channelId, balance1, balance2, nonce, "COOPERATIVE_CLOSE"))`.
+/// Participants sign its EIP-191 form, like channel states.
+pub fn cooperative_close_hash(channel_id: [u8; 32], balance1: U256, balance2: U256, nonce: U256) -> [u8; 32] {
+    use ethers::utils::keccak256;
+    
+    let mut data = Vec::with_capacity(145);
+    let mut word = [0u8; 32];
+    data.extend_from_slice(&channel_id);
+    for value in [balance1, balance2, nonce] {
+        value.to_big_endian(&mut word);
+        data.extend_from_slice(&word);
+    }
+    data.extend_from_slice(b"COOPERATIVE_CLOSE");
+    
+    keccak256(&data)
+}
+
+/// `toEthSignedMessageHash` of a channel hash: the digest that is actually
+/// signed and recovered, as in `PaymentChannel._verifySignature`
+pub fn channel_state_digest(hash: [u8; 32]) -> H256 {
+    ethers::utils::hash_message(hash)
+}
+
+/// Check that `signature` over a channel state was produced by `signer`
+pub fn verify_channel_state_signature(
+    domain: ChannelDomain,
+    signer: Address,
+    channel_id: [u8; 32],
+    balance1: U256,
+    balance2: U256,
+    nonce: U256,
+    signature: &Bytes,
+) -> bool {
+    let digest = channel_state_digest(channel_state_hash(domain, channel_id, balance1, balance2, nonce));
+    Signature::try_from(signature.as_ref())
+        .and_then(|sig| sig.recover(digest))
+        .map(|recovered| recovered == signer)
+        .unwrap_or(false)
+}
+
+/// `(signer, channel_id, balance1, balance2, nonce, signature)` checked by
+/// `verify_channel_state_signatures`
+pub type ChannelSignatureItem = (Address, [u8; 32], U256, U256, U256, Bytes);
+
+/// Below this many items `verify_channel_state_signatures` stays on the calling thread
+const PARALLEL_VERIFY_MIN: usize = 32;
+
+/// `verify_channel_state_signature` for every item within `domain`, spread
+/// across the available CPU cores; results are in input order
+pub fn verify_channel_state_signatures(domain: ChannelDomain, items: &[ChannelSignatureItem]) -> Vec<bool> {
+    let verify = move |item: &ChannelSignatureItem| -> bool {
+        let (signer, channel_id, balance1, balance2, nonce, signature) = item;
+        verify_channel_state_signature(domain, *signer, *channel_id, *balance1, *balance2, *nonce, signature)
+    };
+    
+    let threads = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    if threads == 1 || items.len() < PARALLEL_VERIFY_MIN {
+        return items.iter().map(verify).collect();
+    }
+    
+    let chunk_size = items.len().div_ceil(threads);
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = items
+            .chunks(chunk_size)
+            .map(|chunk| (chunk.len(), scope.spawn(move || chunk.iter().map(verify).collect::<Vec<bool>>())))
+            .collect();
         
-        Ok(receipt.transaction_hash)
+        workers
+            .into_iter()
+            .flat_map(|(len, worker)| worker.join().unwrap_or_else(|_| vec![false; len]))
+            .collect()
+    })
+}
+
+/// Channel state together with the participants' signatures over it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedChannelState {
+    /// Chain and contract the signatures are bound to
+    pub domain: ChannelDomain,
+    pub channel_id: [u8; 32],
+    pub balance1: U256,
+    pub balance2: U256,
+    pub nonce: U256,
+    /// `(signer, signature)` pairs
+    pub signatures: Vec<(Address, Bytes)>,
+}
+
+impl SignedChannelState {
+    /// Hash the participants sign (before the EIP-191 prefix)
+    pub fn hash(&self) -> [u8; 32] {
+        channel_state_hash(self.domain, self.channel_id, self.balance1, self.balance2, self.nonce)
     }
     
-    /// Approve all protocol contracts
-    pub async fn approve_all(&self) -> Result<Vec<H256>> {
-        let max_uint = U256::MAX;
-        let mut hashes = Vec::new();
+    /// Add the wallet's signature over the state
+    pub fn sign(&mut self, wallet: &LocalWallet) -> Result<()> {
+        let signature = wallet.sign_hash(channel_state_digest(self.hash()))?;
+        self.signatures.retain(|(signer, _)| *signer != wallet.address());
+        self.signatures.push((wallet.address(), signature.to_vec().into()));
+        Ok(())
+    }
+    
+    /// Signature attached by `signer`, if any
+    pub fn signature_of(&self, signer: Address) -> Option<&Bytes> {
+        self.signatures.iter().find(|(a, _)| *a == signer).map(|(_, sig)| sig)
+    }
+    
+    /// Hash the participants sign to close the channel at this state
+    pub fn cooperative_close_hash(&self) -> [u8; 32] {
+        cooperative_close_hash(self.channel_id, self.balance1, self.balance2, self.nonce)
+    }
+    
+    /// The wallet's signature agreeing to close the channel at this state
+    pub fn sign_cooperative_close(&self, wallet: &LocalWallet) -> Result<Bytes> {
+        let signature = wallet.sign_hash(channel_state_digest(self.cooperative_close_hash()))?;
+        Ok(signature.to_vec().into())
+    }
+    
+    /// True if `signature` is `signer`'s agreement to close at this state
+    pub fn verify_cooperative_close(&self, signer: Address, signature: &Bytes) -> bool {
+        Signature::try_from(signature.as_ref())
+            .and_then(|sig| sig.recover(channel_state_digest(self.cooperative_close_hash())))
+            .map(|recovered| recovered == signer)
+            .unwrap_or(false)
+    }
+    
+    /// True if there is at least one signature and every one recovers to its signer
+    pub fn verify_signatures(&self) -> bool {
+        !self.signatures.is_empty()
+            && self.signatures.iter().all(|(signer, sig)| {
+                verify_channel_state_signature(self.domain, *signer, self.channel_id, self.balance1, self.balance2, self.nonce, sig)
+            })
+    }
+    
+    /// True if both participants attached a valid signature
+    pub fn is_signed_by(&self, participant1: Address, participant2: Address) -> bool {
+        [participant1, participant2].into_iter().all(|participant| {
+            self.signature_of(participant).is_some_and(|sig| {
+                verify_channel_state_signature(self.domain, participant, self.channel_id, self.balance1, self.balance2, self.nonce, sig)
+            })
+        })
+    }
+}
+
+/// Signed record of how a channel's balances evolved up to its final state
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SettlementSummary {
+    pub channel_id: [u8; 32],
+    /// Balances of the lowest-nonce state seen
+    pub initial_balance1: U256,
+    pub initial_balance2: U256,
+    pub final_balance1: U256,
+    pub final_balance2: U256,
+    pub final_nonce: U256,
+    /// Number of distinct states the summary was built from
+    pub states: usize,
+    /// Total moved from participant 1 to participant 2 across all updates
+    pub paid_by_1: U256,
+    /// Total moved from participant 2 to participant 1 across all updates
+    pub paid_by_2: U256,
+    /// Growth of the channel total between states, i.e. mid-life deposits
+    pub deposited: U256,
+    pub signer: Address,
+    /// Signer's signature over `hash()`
+    pub signature: Bytes,
+}
+
+impl SettlementSummary {
+    /// Rebuild the flow across a channel from its state history.
+    ///
+    /// Every state must carry valid signatures, belong to the final state's
+    /// channel and be no newer than it. States are ordered by nonce; repeats
+    /// of a nonce are dropped, but two different states with the same nonce
+    /// are rejected. Between consecutive states, the amount one side lost and
+    /// the other gained counts as a payment; growth of the total counts as a
+    /// deposit.
+    pub fn from_states(final_state: &SignedChannelState, history: &[SignedChannelState]) -> Result<Self> {
+        if let Some(state) = history.iter().find(|s| s.channel_id != final_state.channel_id) {
+            return Err(SynapseError::InvalidInput(format!(
+                "State for channel 0x{} in history of 0x{}",
+                hex::encode(state.channel_id),
+                hex::encode(final_state.channel_id)
+            )));
+        }
+        if let Some(state) = history.iter().find(|s| s.nonce > final_state.nonce) {
+            return Err(SynapseError::StaleState { current: state.nonce, received: final_state.nonce });
+        }
         
-        let contracts = [
-            self.config.contracts.payment_router,
-            self.config.contracts.reputation,
-            self.config.contracts.service_registry,
-            self.config.contracts.payment_channel,
-        ];
+        let mut states: Vec<&SignedChannelState> = history.iter().chain(std::iter::once(final_state)).collect();
+        if !states.iter().all(|s| s.verify_signatures()) {
+            return Err(SynapseError::InvalidSignature);
+        }
+        states.sort_by_key(|s| s.nonce);
+        if let Some(pair) = states.windows(2).find(|pair| {
+            pair[0].nonce == pair[1].nonce && (pair[0].balance1, pair[0].balance2) != (pair[1].balance1, pair[1].balance2)
+        }) {
+            return Err(SynapseError::InvalidInput(format!("Conflicting states with nonce {}", pair[0].nonce)));
+        }
+        states.dedup_by_key(|s| s.nonce);
         
-        for contract in contracts {
-            let hash = self.approve(contract, max_uint).await?;
-            hashes.push(hash);
+        let overflow = || SynapseError::InvalidInput("Channel balances overflow".to_string());
+        let total = |s: &SignedChannelState| s.balance1.checked_add(s.balance2).ok_or_else(overflow);
+        let (mut paid_by_1, mut paid_by_2, mut deposited) = (U256::zero(), U256::zero(), U256::zero());
+        for pair in states.windows(2) {
+            let (before, after) = (pair[0], pair[1]);
+            if after.balance1 < before.balance1 && after.balance2 > before.balance2 {
+                let paid = (before.balance1 - after.balance1).min(after.balance2 - before.balance2);
+                paid_by_1 = paid_by_1.checked_add(paid).ok_or_else(overflow)?;
+            } else if after.balance2 < before.balance2 && after.balance1 > before.balance1 {
+                let paid = (before.balance2 - after.balance2).min(after.balance1 - before.balance1);
+                paid_by_2 = paid_by_2.checked_add(paid).ok_or_else(overflow)?;
+            }
+            
+            let growth = total(after)?.saturating_sub(total(before)?);
+            deposited = deposited.checked_add(growth).ok_or_else(overflow)?;
         }
         
-        Ok(hashes)
+        Ok(Self {
+            channel_id: final_state.channel_id,
+            initial_balance1: states[0].balance1,
+            initial_balance2: states[0].balance2,
+            final_balance1: final_state.balance1,
+            final_balance2: final_state.balance2,
+            final_nonce: final_state.nonce,
+            states: states.len(),
+            paid_by_1,
+            paid_by_2,
+            deposited,
+            signer: Address::zero(),
+            signature: Bytes::default(),
+        })
     }
     
-    // ==================== Payment Functions ====================
+    /// Canonical summary hash:
+    /// `keccak256(abi.encode(channelId, initial1, initial2, final1, final2, finalNonce, paidBy1, paidBy2, deposited, signer))`
+    pub fn hash(&self) -> [u8; 32] {
+        use ethers::abi::{encode, Token};
+        use ethers::utils::keccak256;
+        
+        keccak256(encode(&[
+            Token::FixedBytes(self.channel_id.to_vec()),
+            Token::Uint(self.initial_balance1),
+            Token::Uint(self.initial_balance2),
+            Token::Uint(self.final_balance1),
+            Token::Uint(self.final_balance2),
+            Token::Uint(self.final_nonce),
+            Token::Uint(self.paid_by_1),
+            Token::Uint(self.paid_by_2),
+            Token::Uint(self.deposited),
+            Token::Address(self.signer),
+        ]))
+    }
     
-    /// Send a payment
+    /// Sign the summary, recording the wallet as its signer
+    pub fn sign(&mut self, wallet: &LocalWallet) -> Result<()> {
+        self.signer = wallet.address();
+        let signature = wallet.sign_hash(H256::from(self.hash()))?;
+        self.signature = signature.to_vec().into();
+        Ok(())
+    }
+    
+    /// True if `signature` was produced by `signer`
+    pub fn verify(&self) -> bool {
+        Signature::try_from(self.signature.as_ref())
+            .and_then(|sig| sig.recover(H256::from(self.hash())))
+            .map(|signer| signer == self.signer)
+            .unwrap_or(false)
+    }
+}
+
+/// Likelihood that a unilateral close with a given state gets overturned
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CloseRisk {
+    /// Not signed by both participants; the contract would reject it
+    Invalid,
+    /// The counterparty is known to hold a higher-nonce state and can win a challenge
+    High,
+    /// The counterparty's latest nonce is unknown
+    Unknown,
+    /// The state is at least as recent as anything the counterparty is known to hold
+    Low,
+}
+
+impl CloseRisk {
+    /// Assess `state` against the highest nonce the counterparty is known to have signed
+    pub fn assess(state: &SignedChannelState, counterparty_last_known_nonce: Option<U256>) -> Self {
+        if state.signatures.len() < 2 || !state.verify_signatures() {
+            return CloseRisk::Invalid;
+        }
+        
+        match counterparty_last_known_nonce {
+            Some(nonce) if nonce > state.nonce => CloseRisk::High,
+            Some(_) => CloseRisk::Low,
+            None => CloseRisk::Unknown,
+        }
+    }
+}
+
+/// Remembers the highest nonce accepted per channel so that a counterparty
+/// cannot make the agent accept an older (regressed) state.
+///
+/// Channels must be registered with their two participants before states
+/// for them are accepted.
+#[derive(Debug, Clone, Default)]
+pub struct ChannelSession {
+    participants: std::collections::HashMap<[u8; 32], (Address, Address)>,
+    accepted: std::collections::HashMap<[u8; 32], U256>,
+}
+
+impl ChannelSession {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Track a channel between `participant1` and `participant2`
+    pub fn register(&mut self, channel_id: [u8; 32], participant1: Address, participant2: Address) {
+        self.participants.insert(channel_id, (participant1, participant2));
+    }
+    
+    /// Accept an incoming state if it is signed by both registered
+    /// participants and its nonce is strictly higher than any previously
+    /// accepted for the channel
+    pub fn accept(&mut self, state: &SignedChannelState) -> Result<()> {
+        let (participant1, participant2) = *self.participants
+            .get(&state.channel_id)
+            .ok_or(SynapseError::ChannelNotFound)?;
+        if !state.is_signed_by(participant1, participant2) {
+            return Err(SynapseError::InvalidSignature);
+        }
+        
+        if let Some(current) = self.accepted.get(&state.channel_id) {
+            if state.nonce <= *current {
+                return Err(SynapseError::StaleState { current: *current, received: state.nonce });
+            }
+        }
+        
+        self.accepted.insert(state.channel_id, state.nonce);
+        Ok(())
+    }
+    
+    /// Highest nonce accepted so far for a channel
+    pub fn latest_nonce(&self, channel_id: [u8; 32]) -> Option<U256> {
+        self.accepted.get(&channel_id).copied()
+    }
+}
+
+/// Validate a burst of channel updates and keep only the latest.
+///
+/// Every update must belong to the same channel, be signed by both
+/// `participant1` and `participant2` (checked with
+/// `verify_channel_state_signatures`) and keep the channel total unchanged,
+/// and nonces must strictly increase in the given order.
+pub fn coalesce_channel_states(
+    participant1: Address,
+    participant2: Address,
+    mut updates: Vec<SignedChannelState>,
+) -> Result<SignedChannelState> {
+    if updates.is_empty() {
+        return Err(SynapseError::InvalidInput("No channel updates to coalesce".to_string()));
+    }
+    let total = |u: &SignedChannelState| u.balance1.checked_add(u.balance2);
+    let (domain, channel_id) = (updates[0].domain, updates[0].channel_id);
+    let expected_total = total(&updates[0])
+        .ok_or(SynapseError::InvalidInput("Channel balances overflow".to_string()))?;
+    
+    for pair in updates.windows(2) {
+        if pair[1].nonce <= pair[0].nonce {
+            return Err(SynapseError::StaleState { current: pair[0].nonce, received: pair[1].nonce });
+        }
+    }
+    if let Some(update) = updates.iter().find(|u| {
+        u.domain != domain || u.channel_id != channel_id || total(*u) != Some(expected_total)
+    }) {
+        return Err(SynapseError::InvalidInput(format!(
+            "Update with nonce {} changes the channel or its total",
+            update.nonce
+        )));
+    }
+    
+    let mut items: Vec<ChannelSignatureItem> = Vec::with_capacity(updates.len() * 2);
+    for update in &updates {
+        for participant in [participant1, participant2] {
+            let signature = update.signature_of(participant).ok_or(SynapseError::InvalidSignature)?;
+            items.push((participant, update.channel_id, update.balance1, update.balance2, update.nonce, signature.clone()));
+        }
+    }
+    if !verify_channel_state_signatures(domain, &items).into_iter().all(|valid| valid) {
+        return Err(SynapseError::InvalidSignature);
+    }
+    
+    Ok(updates.swap_remove(updates.len() - 1))
+}
+
+/// Tracks the last successful off-chain exchange per channel to detect an
+/// unresponsive counterparty.
+///
+/// A stale channel should be closed unilaterally (`initiate_close` with the
+/// latest mutually signed state) before the counterparty can act on an older one.
+#[derive(Debug, Clone, Default)]
+pub struct ChannelHeartbeat {
+    last_activity: HashMap<[u8; 32], Instant>,
+}
+
+impl ChannelHeartbeat {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Record a successful exchange with the counterparty of a channel
+    pub fn record_activity(&mut self, channel_id: [u8; 32]) {
+        self.last_activity.insert(channel_id, Instant::now());
+    }
+    
+    /// True if the channel is tracked and its last exchange is older than
+    /// `threshold`
+    pub fn is_counterparty_stale(&self, channel_id: [u8; 32], threshold: Duration) -> bool {
+        self.last_activity
+            .get(&channel_id)
+            .map(|at| at.elapsed() > threshold)
+            .unwrap_or(false)
+    }
+    
+    /// Tracked channels whose counterparty has been silent longer than `threshold`
+    pub fn stale_channels(&self, threshold: Duration) -> Vec<[u8; 32]> {
+        self.last_activity.iter()
+            .filter(|(_, at)| at.elapsed() > threshold)
+            .map(|(id, _)| *id)
+            .collect()
+    }
+    
+    /// Stop tracking a closed channel
+    pub fn forget(&mut self, channel_id: [u8; 32]) {
+        self.last_activity.remove(&channel_id);
+    }
+}
+
+/// Capability negotiation protocol version spoken by this SDK
+pub const CAPABILITIES_VERSION: u32 = 1;
+
+/// Features an agent supports, exchanged off-chain before transacting
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Capabilities {
+    pub version: u32,
+    /// Accepted pricing models, most preferred first
+    pub pricing_models: Vec<PricingModel>,
+    pub channels: bool,
+    pub streams: bool,
+    pub escrow: bool,
+    /// Payment metadata schemas understood, most preferred first
+    pub metadata_schemas: Vec<String>,
+}
+
+impl Capabilities {
+    /// Everything this SDK can handle
+    pub fn sdk() -> Self {
+        let mut metadata_schemas = vec!["compute-receipt/1".to_string(), "tip/1".to_string()];
+        if cfg!(feature = "encryption") {
+            metadata_schemas.push("ecies/1".to_string());
+        }
+        
+        Self {
+            version: CAPABILITIES_VERSION,
+            pricing_models: vec![
+                PricingModel::PerRequest,
+                PricingModel::PerToken,
+                PricingModel::PerSecond,
+                PricingModel::PerByte,
+                PricingModel::Subscription,
+                PricingModel::Custom,
+            ],
+            channels: true,
+            streams: true,
+            escrow: true,
+            metadata_schemas,
+        }
+    }
+    
+    /// Intersect with a counterparty's capabilities, keeping this side's
+    /// order of preference
+    pub fn intersect(&self, other: &Capabilities) -> NegotiatedSession {
+        let pricing_models: Vec<PricingModel> = self.pricing_models.iter()
+            .filter(|model| other.pricing_models.contains(model))
+            .copied()
+            .collect();
+        let metadata_schemas: Vec<String> = self.metadata_schemas.iter()
+            .filter(|schema| other.metadata_schemas.contains(schema))
+            .cloned()
+            .collect();
+        
+        NegotiatedSession {
+            version: self.version.min(other.version),
+            pricing_model: pricing_models.first().copied(),
+            pricing_models,
+            channels: self.channels && other.channels,
+            streams: self.streams && other.streams,
+            escrow: self.escrow && other.escrow,
+            metadata_schema: metadata_schemas.first().cloned(),
+        }
+    }
+}
+
+/// Protocol parameters both agents agreed on, from `negotiate`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NegotiatedSession {
+    /// Lower of the two protocol versions
+    pub version: u32,
+    /// Preferred common pricing model; `None` if there is none
+    pub pricing_model: Option<PricingModel>,
+    /// All common pricing models
+    pub pricing_models: Vec<PricingModel>,
+    pub channels: bool,
+    pub streams: bool,
+    pub escrow: bool,
+    /// Preferred common metadata schema; `None` means plain metadata only
+    pub metadata_schema: Option<String>,
+}
+
+impl NegotiatedSession {
+    /// True if the agents share at least one pricing model to transact with
+    pub fn is_viable(&self) -> bool {
+        self.pricing_model.is_some()
+    }
+}
+
+/// Invoice line item
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InvoiceLineItem {
+    pub description: String,
+    pub quantity: U256,
+    pub unit_price: U256,
+}
+
+/// Provider-signed invoice referenced by a payment
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Invoice {
+    pub id: String,
+    pub provider: Address,
+    pub line_items: Vec<InvoiceLineItem>,
+    /// Provider's signature over `hash()`
+    pub signature: Bytes,
+}
+
+impl Invoice {
+    /// Canonical invoice hash:
+    /// `keccak256(abi.encode(id, provider, (description, quantity, unitPrice)[]))`
+    pub fn hash(&self) -> [u8; 32] {
+        use ethers::abi::{encode, Token};
+        use ethers::utils::keccak256;
+        
+        let items = self.line_items.iter()
+            .map(|item| Token::Tuple(vec![
+                Token::String(item.description.clone()),
+                Token::Uint(item.quantity),
+                Token::Uint(item.unit_price),
+            ]))
+            .collect();
+        
+        keccak256(encode(&[
+            Token::String(self.id.clone()),
+            Token::Address(self.provider),
+            Token::Array(items),
+        ]))
+    }
+    
+    /// Sum of `quantity * unit_price` over all line items
+    pub fn total(&self) -> U256 {
+        self.line_items.iter()
+            .fold(U256::zero(), |total, item| total.saturating_add(item.quantity.saturating_mul(item.unit_price)))
+    }
+    
+    /// Sign the invoice as its provider
+    pub fn sign(&mut self, wallet: &LocalWallet) -> Result<()> {
+        let signature = wallet.sign_hash(H256::from(self.hash()))?;
+        self.signature = signature.to_vec().into();
+        Ok(())
+    }
+    
+    /// True if `signature` was produced by `provider`
+    pub fn verify(&self) -> bool {
+        Signature::try_from(self.signature.as_ref())
+            .and_then(|sig| sig.recover(H256::from(self.hash())))
+            .map(|signer| signer == self.provider)
+            .unwrap_or(false)
+    }
+}
+
+/// Provider-signed acknowledgement of a payment it received
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedReceipt {
+    pub payment_id: H256,
+    /// Transaction that carried the payment
+    pub tx_hash: H256,
+    pub payer: Address,
+    pub provider: Address,
+    pub amount: U256,
+    /// Provider's signature over `hash()`
+    pub signature: Bytes,
+}
+
+impl SignedReceipt {
+    /// Canonical receipt hash:
+    /// `keccak256(abi.encode(paymentId, txHash, payer, provider, amount))`
+    pub fn hash(&self) -> [u8; 32] {
+        use ethers::abi::{encode, Token};
+        use ethers::utils::keccak256;
+        
+        keccak256(encode(&[
+            Token::FixedBytes(self.payment_id.as_bytes().to_vec()),
+            Token::FixedBytes(self.tx_hash.as_bytes().to_vec()),
+            Token::Address(self.payer),
+            Token::Address(self.provider),
+            Token::Uint(self.amount),
+        ]))
+    }
+    
+    /// True if `signature` was produced by `provider`
+    pub fn verify(&self) -> bool {
+        Signature::try_from(self.signature.as_ref())
+            .and_then(|sig| sig.recover(H256::from(self.hash())))
+            .map(|signer| signer == self.provider)
+            .unwrap_or(false)
+    }
+}
+
+/// Hash of a delivered service result for `DeliveryProof`: `keccak256` of
+/// the exact response bytes returned to the payer (e.g. the HTTP body), so
+/// either side can recompute it from what was sent or received
+pub fn delivery_result_hash(result: &[u8]) -> [u8; 32] {
+    ethers::utils::keccak256(result)
+}
+
+/// Provider-signed statement that the result with `result_hash` was
+/// delivered for `payment_id`; evidence for escrow arbitration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeliveryProof {
+    pub payment_id: H256,
+    /// `delivery_result_hash` of the delivered result
+    pub result_hash: H256,
+    pub provider: Address,
+    /// Provider's signature over `hash()`
+    pub signature: Bytes,
+}
+
+impl DeliveryProof {
+    /// Canonical proof hash:
+    /// `keccak256(abi.encode("SYNAPSE_DELIVERY", paymentId, resultHash, provider))`
+    pub fn hash(&self) -> [u8; 32] {
+        use ethers::abi::{encode, Token};
+        use ethers::utils::keccak256;
+        
+        keccak256(encode(&[
+            Token::String("SYNAPSE_DELIVERY".to_string()),
+            Token::FixedBytes(self.payment_id.as_bytes().to_vec()),
+            Token::FixedBytes(self.result_hash.as_bytes().to_vec()),
+            Token::Address(self.provider),
+        ]))
+    }
+    
+    /// Sign the proof as its provider
+    pub fn sign(&mut self, wallet: &LocalWallet) -> Result<()> {
+        let signature = wallet.sign_hash(H256::from(self.hash()))?;
+        self.signature = signature.to_vec().into();
+        Ok(())
+    }
+    
+    /// True if `signature` was produced by `provider`
+    pub fn verify(&self) -> bool {
+        Signature::try_from(self.signature.as_ref())
+            .and_then(|sig| sig.recover(H256::from(self.hash())))
+            .map(|signer| signer == self.provider)
+            .unwrap_or(false)
+    }
+}
+
+/// Signed statement that an address holds a given SYNAPSE agent registration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IdentityClaim {
+    pub agent: Address,
+    pub name: String,
+    /// Verifier-chosen challenge, prevents replaying old claims
+    pub nonce: [u8; 32],
+    pub chain_id: u64,
+    /// Reputation registry the registration lives in
+    pub registry: Address,
+    /// Agent's signature over `hash()`
+    pub signature: Bytes,
+}
+
+impl IdentityClaim {
+    /// Canonical claim hash:
+    /// `keccak256(abi.encode("SYNAPSE_IDENTITY_CLAIM", chainId, registry, agent, name, nonce))`
+    pub fn hash(&self) -> [u8; 32] {
+        use ethers::abi::{encode, Token};
+        use ethers::utils::keccak256;
+        
+        keccak256(encode(&[
+            Token::String("SYNAPSE_IDENTITY_CLAIM".to_string()),
+            Token::Uint(U256::from(self.chain_id)),
+            Token::Address(self.registry),
+            Token::Address(self.agent),
+            Token::String(self.name.clone()),
+            Token::FixedBytes(self.nonce.to_vec()),
+        ]))
+    }
+    
+    /// True if `signature` was produced by `agent`
+    pub fn verify_signature(&self) -> bool {
+        Signature::try_from(self.signature.as_ref())
+            .and_then(|sig| sig.recover(H256::from(self.hash())))
+            .map(|signer| signer == self.agent)
+            .unwrap_or(false)
+    }
+}
+
+/// Result of reconciling a batch payout against its intended manifest
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchAudit {
+    /// Expected payments with no on-chain counterpart
+    pub missing: Vec<(Address, U256)>,
+    /// On-chain payments that were not in the manifest
+    pub extra: Vec<(Address, U256)>,
+    /// Recipients paid a different amount: `(recipient, expected, actual)`
+    pub mismatched: Vec<(Address, U256, U256)>,
+}
+
+impl BatchAudit {
+    /// Diff the expected `(recipient, amount)` pairs against the actual ones
+    pub fn compare(expected: &[(Address, U256)], actual: &[(Address, U256)]) -> Self {
+        let mut used = vec![false; actual.len()];
+        let mut unmatched = Vec::new();
+        
+        for &(recipient, amount) in expected {
+            match actual.iter().enumerate().position(|(i, a)| !used[i] && *a == (recipient, amount)) {
+                Some(i) => used[i] = true,
+                None => unmatched.push((recipient, amount)),
+            }
+        }
+        
+        let mut audit = BatchAudit::default();
+        for (recipient, amount) in unmatched {
+            match actual.iter().enumerate().position(|(i, a)| !used[i] && a.0 == recipient) {
+                Some(i) => {
+                    used[i] = true;
+                    audit.mismatched.push((recipient, amount, actual[i].1));
+                }
+                None => audit.missing.push((recipient, amount)),
+            }
+        }
+        
+        audit.extra = actual.iter().zip(used).filter(|(_, u)| !u).map(|(a, _)| *a).collect();
+        audit
+    }
+    
+    /// True when the payout matches the manifest exactly
+    pub fn is_clean(&self) -> bool {
+        self.missing.is_empty() && self.extra.is_empty() && self.mismatched.is_empty()
+    }
+}
+
+/// Position of an event on chain; indexers persist the last one they processed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct EventCheckpoint {
+    pub block: u64,
+    pub log_index: u64,
+}
+
+impl EventCheckpoint {
+    pub fn new(block: u64, log_index: u64) -> Self {
+        Self { block, log_index }
+    }
+}
+
+/// Decoded `Payment` event with its on-chain position
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PaymentEvent {
+    pub payment_id: H256,
+    pub sender: Address,
+    pub recipient: Address,
+    pub amount: U256,
+    pub fee: U256,
+    pub tx_hash: H256,
+    pub block_number: u64,
+    pub log_index: u64,
+}
+
+impl PaymentEvent {
+    fn from_log(event: PaymentFilter, meta: LogMeta) -> Self {
+        Self {
+            payment_id: event.payment_id.into(),
+            sender: event.sender,
+            recipient: event.recipient,
+            amount: event.amount,
+            fee: event.fee,
+            tx_hash: meta.transaction_hash,
+            block_number: meta.block_number.as_u64(),
+            log_index: meta.log_index.as_u64(),
+        }
+    }
+    
+    /// Decode a raw `Payment` log
+    fn decode(log: Log) -> Option<Self> {
+        let meta = LogMeta::from(&log);
+        let event = parse_log::<PaymentFilter>(log).ok()?;
+        Some(Self::from_log(event, meta))
+    }
+    
+    /// Amount the recipient actually received (amount minus fee)
+    pub fn net_amount(&self) -> U256 {
+        self.amount.saturating_sub(self.fee)
+    }
+    
+    /// Checkpoint marking this event as processed
+    pub fn checkpoint(&self) -> EventCheckpoint {
+        EventCheckpoint::new(self.block_number, self.log_index)
+    }
+}
+
+/// What happened to a channel in a `ChannelEvent`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChannelEventKind {
+    Opened { party1: Address, party2: Address, deposit1: U256, deposit2: U256 },
+    Deposit { party: Address, amount: U256 },
+    CloseInitiated { initiator: Address, balance1: U256, balance2: U256, nonce: U256 },
+    Challenged { challenger: Address, nonce: U256 },
+    Closed { balance1: U256, balance2: U256 },
+    Disputed,
+}
+
+impl From<PaymentChannelEvents> for ChannelEventKind {
+    fn from(event: PaymentChannelEvents) -> Self {
+        match event {
+            PaymentChannelEvents::ChannelOpenedFilter(e) => ChannelEventKind::Opened {
+                party1: e.party_1,
+                party2: e.party_2,
+                deposit1: e.deposit_1,
+                deposit2: e.deposit_2,
+            },
+            PaymentChannelEvents::ChannelDepositFilter(e) => ChannelEventKind::Deposit {
+                party: e.party,
+                amount: e.amount,
+            },
+            PaymentChannelEvents::ChannelCloseInitiatedFilter(e) => ChannelEventKind::CloseInitiated {
+                initiator: e.initiator,
+                balance1: e.balance_a,
+                balance2: e.balance_b,
+                nonce: e.nonce,
+            },
+            PaymentChannelEvents::ChannelChallengedFilter(e) => ChannelEventKind::Challenged {
+                challenger: e.challenger,
+                nonce: e.new_nonce,
+            },
+            PaymentChannelEvents::ChannelClosedFilter(e) => ChannelEventKind::Closed {
+                balance1: e.final_balance_1,
+                balance2: e.final_balance_2,
+            },
+            PaymentChannelEvents::ChannelDisputedFilter(_) => ChannelEventKind::Disputed,
+        }
+    }
+}
+
+/// Decoded channel lifecycle event with its on-chain position
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChannelEvent {
+    pub channel_id: H256,
+    pub kind: ChannelEventKind,
+    pub tx_hash: H256,
+    pub block_number: u64,
+    pub log_index: u64,
+}
+
+impl ChannelEvent {
+    /// Position of this event in the chain
+    pub fn checkpoint(&self) -> EventCheckpoint {
+        EventCheckpoint::new(self.block_number, self.log_index)
+    }
+}
+
+/// Selectors probed by `analyze_batch_mev` to recognise AMM pools:
+/// Uniswap V2 `getReserves()` and Uniswap V3 `slot0()`
+const AMM_POOL_PROBES: [[u8; 4]; 2] = [[0x09, 0x02, 0xf1, 0xac], [0x38, 0x50, 0xc7, 0xbd]];
+
+/// Recent blocks sampled by `suggest_priority_fee`
+const FEE_HISTORY_BLOCKS: u64 = 20;
+
+/// Maximum block span requested per `eth_getLogs` call
+const LOG_CHUNK_SIZE: u64 = 5_000;
+
+/// Signer-backed HTTP middleware used by `SynapseClient::new`
+type SignerClient = SignerMiddleware<Provider<Http>, LocalWallet>;
+
+/// Cached view results keyed by target, calldata and block
+type ViewCache = Mutex<HashMap<String, (Instant, Arc<dyn Any + Send + Sync>)>>;
+
+/// SYNAPSE Protocol Client
+pub struct SynapseClient<M: Middleware> {
+    provider: Arc<M>,
+    wallet: LocalWallet,
+    config: Config,
+    view_cache: ViewCache,
+    /// Next nonce to use; `None` until first use or after a failed send
+    next_nonce: tokio::sync::Mutex<Option<U256>>,
+    /// Services taken offline by `pause_all_services`, for `resume_all_services`
+    paused_services: Mutex<Vec<[u8; 32]>>,
+    token: SynapseToken<M>,
+    router: PaymentRouter<M>,
+    reputation: ReputationRegistry<M>,
+    services: ServiceRegistry<M>,
+    channels: PaymentChannel<M>,
+}
+
+impl SynapseClient<SignerClient> {
+    /// Create a new client
+    pub async fn new(
+        rpc_url: &str,
+        private_key: &str,
+        contracts: ContractAddresses,
+    ) -> Result<Self> {
+        let provider = Provider::<Http>::try_from(rpc_url)
+            .map_err(|e| SynapseError::ConfigError(e.to_string()))?;
+        
+        let chain_id = provider.get_chainid().await?;
+        
+        let wallet: LocalWallet = private_key
+            .parse::<LocalWallet>()
+            .map_err(|e| SynapseError::ConfigError(e.to_string()))?
+            .with_chain_id(chain_id.as_u64());
+        
+        let client = SignerMiddleware::new(provider, wallet.clone());
+        let client = Arc::new(client);
+        
+        let token = SynapseToken::new(contracts.token, client.clone());
+        let router = PaymentRouter::new(contracts.payment_router, client.clone());
+        let reputation = ReputationRegistry::new(contracts.reputation, client.clone());
+        let services = ServiceRegistry::new(contracts.service_registry, client.clone());
+        let channels = PaymentChannel::new(contracts.payment_channel, client.clone());
+        
+        let config = Config {
+            rpc_url: rpc_url.to_string(),
+            chain_id: chain_id.as_u64(),
+            contracts,
+            ipfs_gateway: default_ipfs_gateway(),
+            confirmations: default_confirmations(),
+            gas_price_multiplier: default_gas_price_multiplier(),
+            deployment_block: default_deployment_block(),
+            batcher: default_batcher(),
+            view_retries: default_view_retries(),
+            view_cache_ttl: default_view_cache_ttl(),
+            payer: default_payer(),
+            gas_model: GasModel::default(),
+            max_stream_backdate: default_max_stream_backdate(),
+        };
+        
+        Ok(Self {
+            provider: client,
+            wallet,
+            config,
+            view_cache: Mutex::new(HashMap::new()),
+            next_nonce: tokio::sync::Mutex::new(None),
+            paused_services: Mutex::new(Vec::new()),
+            token,
+            router,
+            reputation,
+            services,
+            channels,
+        })
+    }
+    
+    /// Get the client's address
+    pub fn address(&self) -> Address {
+        self.wallet.address()
+    }
+    
+    /// Get chain ID
+    pub fn chain_id(&self) -> u64 {
+        self.config.chain_id
+    }
+    
+    /// Set the gateway used to resolve `ipfs://` metadata URIs
+    pub fn with_ipfs_gateway(mut self, gateway: &str) -> Self {
+        self.config.ipfs_gateway = gateway.to_string();
+        self
+    }
+    
+    /// Set the number of confirmations to wait for after each transaction
+    pub fn with_confirmations(mut self, confirmations: usize) -> Self {
+        self.config.confirmations = confirmations.max(1);
+        self
+    }
+    
+    /// Pay above the estimated gas price for faster inclusion.
+    ///
+    /// The multiplier scales the estimated `max_fee_per_gas` and
+    /// `max_priority_fee_per_gas` (or `gas_price` under `GasModel::Legacy`)
+    /// of every transaction the client sends. Calls
+    /// that already carry explicit fees are sent as-is, so explicit gas
+    /// settings always take precedence over the multiplier.
+    pub fn with_gas_price_multiplier(mut self, multiplier: f64) -> Self {
+        self.config.gas_price_multiplier = if multiplier.is_finite() { multiplier.max(1.0) } else { 1.0 };
+        self
+    }
+    
+    /// Set the protocol deployment block used as the start of event scans
+    pub fn with_deployment_block(mut self, block: u64) -> Self {
+        self.config.deployment_block = block;
+        self
+    }
+    
+    /// Set the batching forwarder used by `execute_batch`.
+    ///
+    /// The forwarder must be registered as an ERC-2771 trusted forwarder on
+    /// the protocol contracts so that each inner call is attributed to this
+    /// wallet rather than to the forwarder.
+    pub fn with_batcher(mut self, batcher: Address) -> Self {
+        self.config.batcher = Some(batcher);
+        self
+    }
+    
+    /// Set how many times a failing view call is retried.
+    ///
+    /// Only reads are retried; transactions are never resubmitted automatically.
+    pub fn with_view_retries(mut self, retries: u32) -> Self {
+        self.config.view_retries = retries;
+        self
+    }
+    
+    /// Cache view call results for `ttl`.
+    ///
+    /// The cache is cleared whenever this client sends a transaction and
+    /// again once it is mined, so reads after the client's own writes are
+    /// fresh; writes by other accounts can still be up to `ttl` stale.
+    pub fn with_view_cache(mut self, ttl: Duration) -> Self {
+        self.config.view_cache_ttl = Some(ttl);
+        self
+    }
+    
+    /// Allow new streams to start up to `window` before the latest block
+    pub fn with_max_stream_backdate(mut self, window: Duration) -> Self {
+        self.config.max_stream_backdate = window;
+        self
+    }
+    
+    /// Spend from a separate treasury instead of the signing wallet.
+    ///
+    /// Before each transfer, payment, escrow, stream, channel deposit or
+    /// stake increase the client pulls the exact amount from `payer` with
+    /// `transferFrom`, then spends it as usual. The treasury must therefore
+    /// call `approve(agent_address, limit)` on the SYNX token, where `limit`
+    /// caps what the hot key can ever pull. The agent still needs its own
+    /// approvals for the spending contracts (e.g. `approve_for_plan`).
+    ///
+    /// Funding and spend are two separate transactions, not one atomic
+    /// operation: if the spend fails after the pull was mined, the funds stay
+    /// in the signing wallet and the error is `FundedSpendFailed`, carrying
+    /// the funding transaction hash for reconciliation.
+    pub fn with_payer(mut self, payer: Address) -> Self {
+        self.config.payer = Some(payer);
+        self
+    }
+    
+    /// Treasury funding this agent, if delegated spending is enabled
+    pub fn payer(&self) -> Option<Address> {
+        self.config.payer
+    }
+    
+    /// Select legacy or EIP-1559 transactions for every send
+    pub fn with_gas_model(mut self, gas_model: GasModel) -> Self {
+        self.config.gas_model = gas_model;
+        self
+    }
+    
+    // ==================== Network Functions ====================
+    
+    /// Measure the average block time over the last `samples` blocks
+    pub async fn measure_block_time(&self, samples: usize) -> Result<Duration> {
+        let provider = self.provider.provider();
+        let latest = provider.get_block_number().await?.as_u64();
+        let samples = (samples.max(1) as u64).min(latest);
+        if samples == 0 {
+            return Err(SynapseError::ConfigError("Not enough blocks to sample".to_string()));
+        }
+        
+        let newest = provider.get_block(latest).await?
+            .ok_or(SynapseError::ContractError(format!("Block {} not found", latest)))?;
+        let oldest = provider.get_block(latest - samples).await?
+            .ok_or(SynapseError::ContractError(format!("Block {} not found", latest - samples)))?;
+        
+        let elapsed = newest.timestamp.saturating_sub(oldest.timestamp).as_u64();
+        Ok(Duration::from_millis(elapsed * 1000 / samples))
+    }
+    
+    /// Built-in profile of the configured chain; see `chain_profile_for`
+    pub fn chain_profile(&self) -> ChainProfile {
+        chain_profile_for(self.config.chain_id)
+    }
+    
+    /// Timestamp of the latest block, the clock contracts compare deadlines against
+    pub async fn chain_time(&self) -> Result<U256> {
+        let block = self.provider.provider().get_block(BlockNumber::Latest).await?
+            .ok_or(SynapseError::ContractError("Latest block not found".to_string()))?;
+        Ok(block.timestamp)
+    }
+    
+    /// Estimate the time until a transaction reaches the configured confirmations
+    pub async fn estimated_finality(&self) -> Result<Duration> {
+        let block_time = self.measure_block_time(20).await?;
+        Ok(block_time * self.config.confirmations as u32)
+    }
+    
+    /// Priority fee paid at `percentile` (0-100) of recent blocks.
+    ///
+    /// Reads the percentile of each of the last blocks via `eth_feeHistory`
+    /// and returns the median across blocks, so one outlier block does not
+    /// skew the suggestion.
+    pub async fn suggest_priority_fee(&self, percentile: f64) -> Result<U256> {
+        if !(0.0..=100.0).contains(&percentile) {
+            return Err(SynapseError::InvalidInput(format!("Percentile {} outside 0-100", percentile)));
+        }
+        
+        let history = self.provider.provider()
+            .fee_history(FEE_HISTORY_BLOCKS, BlockNumber::Latest, &[percentile])
+            .await?;
+        
+        let mut rewards: Vec<U256> = history.reward.iter()
+            .filter_map(|block| block.first().copied())
+            .collect();
+        if rewards.is_empty() {
+            return Err(SynapseError::ContractError("Node returned no priority fee history".to_string()));
+        }
+        rewards.sort();
+        
+        Ok(rewards[rewards.len() / 2])
+    }
+    
+    /// Realign the client's nonce tracking with the chain's pending
+    /// transaction count, e.g. after the same key was used by other tooling.
+    /// Returns the nonce the next transaction will use.
+    pub async fn resync_nonce(&self) -> Result<U256> {
+        let mut next = self.next_nonce.lock().await;
+        let nonce = self.pending_nonce().await?;
+        *next = Some(nonce);
+        Ok(nonce)
+    }
+    
+    /// Pending transaction count of the wallet
+    async fn pending_nonce(&self) -> Result<U256> {
+        let nonce = self.provider.provider()
+            .get_transaction_count(self.address(), Some(BlockNumber::Pending.into()))
+            .await?;
+        Ok(nonce)
+    }
+    
+    /// Reserve the next nonce, initialising from the chain on first use
+    async fn reserve_nonce(&self) -> Result<U256> {
+        let mut next = self.next_nonce.lock().await;
+        let nonce = match *next {
+            Some(nonce) => nonce,
+            None => self.pending_nonce().await?,
+        };
+        *next = Some(nonce + 1);
+        Ok(nonce)
+    }
+    
+    /// Forget the tracked nonce so the next send re-reads it from the chain
+    async fn invalidate_nonce(&self) {
+        *self.next_nonce.lock().await = None;
+    }
+    
+    /// Cross-check the configured contract addresses against each other.
+    ///
+    /// Every protocol contract must point at `contracts.token`, and the
+    /// router's reputation registry (if set) at `contracts.reputation`.
+    /// A mismatch usually means one address was swapped for another
+    /// deployment, possibly a malicious clone; all mismatches are reported
+    /// in a single `ConfigError`.
+    pub async fn verify_contracts(&self) -> Result<()> {
+        let contracts = &self.config.contracts;
+        let (router_token, router_registry, reputation_token, services_token, channel_token) = futures::try_join!(
+            self.call_with_retry(self.router.synx_token()),
+            self.call_with_retry(self.router.reputation_registry()),
+            self.call_with_retry(self.reputation.synx_token()),
+            self.call_with_retry(self.services.synx_token()),
+            self.call_with_retry(self.channels.synx_token()),
+        )?;
+        
+        let mut mismatches = Vec::new();
+        for (name, token) in [
+            ("payment_router", router_token),
+            ("reputation", reputation_token),
+            ("service_registry", services_token),
+            ("payment_channel", channel_token),
+        ] {
+            if token != contracts.token {
+                mismatches.push(format!("{} uses token {:?}, expected {:?}", name, token, contracts.token));
+            }
+        }
+        if router_registry != Address::zero() && router_registry != contracts.reputation {
+            mismatches.push(format!(
+                "payment_router uses reputation registry {:?}, expected {:?}",
+                router_registry, contracts.reputation
+            ));
+        }
+        
+        if mismatches.is_empty() {
+            Ok(())
+        } else {
+            Err(SynapseError::ConfigError(mismatches.join("; ")))
+        }
+    }
+    
+    /// Gather balances, registration, allowances and nonce status for debugging
+    pub async fn diagnostic_snapshot(&self) -> Result<DiagnosticSnapshot> {
+        let me = self.address();
+        let provider = self.provider.provider();
+        
+        let (block_number, native_balance, confirmed_nonce, pending_nonce) = futures::try_join!(
+            provider.get_block_number(),
+            provider.get_balance(me, None),
+            provider.get_transaction_count(me, Some(BlockNumber::Latest.into())),
+            provider.get_transaction_count(me, Some(BlockNumber::Pending.into())),
+        )?;
+        let (synx_balance, agent, allowances) = futures::try_join!(
+            self.balance(),
+            self.get_agent(me),
+            self.protocol_allowances(),
+        )?;
+        
+        Ok(DiagnosticSnapshot {
+            address: me,
+            chain_id: self.config.chain_id,
+            block_number: block_number.as_u64(),
+            synx_balance,
+            native_balance,
+            agent,
+            allowances,
+            confirmed_nonce,
+            pending_nonce,
+        })
+    }
+    
+    /// Capture a reverted transaction so it can be sent again with `retry_with`
+    pub async fn failed_tx(&self, tx_hash: H256) -> Result<FailedTx> {
+        let provider = self.provider.provider();
+        let (tx, receipt) = futures::try_join!(
+            provider.get_transaction(tx_hash),
+            provider.get_transaction_receipt(tx_hash),
+        )?;
+        let tx = tx.ok_or_else(|| SynapseError::TransactionFailed(format!("{:?} not found", tx_hash)))?;
+        let receipt = receipt.ok_or_else(|| SynapseError::TransactionFailed(format!("{:?} not mined", tx_hash)))?;
+        
+        if receipt.status == Some(U64::one()) {
+            return Err(SynapseError::InvalidInput(format!("{:?} did not revert", tx_hash)));
+        }
+        let to = tx.to.ok_or_else(|| SynapseError::InvalidInput("Contract creations cannot be retried".to_string()))?;
+        
+        Ok(FailedTx {
+            tx_hash,
+            to,
+            data: tx.input,
+            value: tx.value,
+            gas_limit: tx.gas,
+            gas_used: receipt.gas_used.unwrap_or_default(),
+        })
+    }
+    
+    /// Send a reverted transaction again as a new transaction with
+    /// `adjustments` applied, under a fresh nonce and current gas prices.
+    ///
+    /// This is for calls that reverted for a fixable reason; stuck pending
+    /// transactions need a same-nonce replacement instead.
+    pub async fn retry_with(&self, original: &FailedTx, adjustments: TxAdjustments) -> Result<H256> {
+        let mut tx: TypedTransaction = match self.config.gas_model {
+            GasModel::Legacy => TransactionRequest::new().into(),
+            GasModel::Eip1559 => Eip1559TransactionRequest::new().into(),
+        };
+        tx.set_from(self.address());
+        tx.set_to(original.to);
+        tx.set_data(adjustments.data.unwrap_or_else(|| original.data.clone()));
+        tx.set_value(adjustments.value.unwrap_or(original.value));
+        if let Some(gas_limit) = adjustments.gas_limit {
+            tx.set_gas(gas_limit);
+        }
+        
+        self.submit_tx(tx).await
+    }
+    
+    /// Aggregate this wallet's payments, channels, streams and reputation
+    /// since `from_block` into one summary
+    pub async fn agent_metrics(&self, from_block: u64) -> Result<AgentMetrics> {
+        let me = H256::from(self.address());
+        let head = self.provider.provider().get_block_number().await?.as_u64();
+        
+        let sent_filter = self.router.event::<PaymentFilter>().topic1(me).filter;
+        let received_filter = self.router.event::<PaymentFilter>().topic2(me).filter;
+        let streams_sent = self.router.event::<StreamCreatedFilter>().topic2(me).filter;
+        let streams_received = self.router.event::<StreamCreatedFilter>().topic3(me).filter;
+        let (sent, received, outgoing, incoming, channels, agent) = futures::try_join!(
+            self.payment_events(sent_filter, from_block, head),
+            self.payment_events(received_filter, from_block, head),
+            self.scan_logs::<StreamCreatedFilter>(streams_sent, from_block, head),
+            self.scan_logs::<StreamCreatedFilter>(streams_received, from_block, head),
+            self.my_open_channels(from_block),
+            self.get_agent(self.address()),
+        )?;
+        
+        let streams = futures::future::try_join_all(
+            outgoing.iter().chain(&incoming).map(|(event, _)| self.get_stream(event.stream_id)),
+        )
+        .await?;
+        
+        let sum = |payments: &[PaymentEvent], amount: fn(&PaymentEvent) -> U256| {
+            payments.iter().fold(U256::zero(), |total, p| total.saturating_add(amount(p)))
+        };
+        
+        Ok(AgentMetrics {
+            from_block,
+            to_block: head,
+            total_paid: sum(&sent, |p| p.amount),
+            total_received: sum(&received, |p| p.amount.saturating_sub(p.fee)),
+            total_fees: sum(&sent, |p| p.fee),
+            payments_sent: sent.len(),
+            payments_received: received.len(),
+            open_channels: channels.len(),
+            active_streams: streams.iter().filter(|stream| stream.active).count(),
+            agent,
+        })
+    }
+    
+    // ==================== Token Functions ====================
+    
+    /// Get token balance
+    pub async fn get_balance(&self, address: Address) -> Result<U256> {
+        let balance = self.call_with_retry(self.token.balance_of(address)).await?;
+        Ok(balance)
+    }
+    
+    /// Get own balance
+    pub async fn balance(&self) -> Result<U256> {
+        self.get_balance(self.address()).await
+    }
+    
+    /// Transfer tokens, funded from the payer if one is configured
+    pub async fn transfer(&self, to: Address, amount: impl Into<U256>) -> Result<H256> {
+        let amount = amount.into();
+        let funding_tx = self.fund_from_payer(amount).await?;
+        
+        let call = self.token.transfer(to, amount);
+        let receipt = self.send_call(call).await.map_err(funded(funding_tx))?;
+        
+        Ok(receipt.transaction_hash)
+    }
+    
+    /// Transfer the whole SYNX balance to `destination` if it exceeds
+    /// `min_balance`; returns `None` without sending when it does not
+    pub async fn sweep_to(&self, destination: Address, min_balance: U256) -> Result<Option<H256>> {
+        let balance = self.balance().await?;
+        if balance.is_zero() || balance <= min_balance {
+            return Ok(None);
+        }
+        
+        // The wallet's own balance, so never funded from the payer
+        let receipt = self.send_call(self.token.transfer(destination, balance)).await?;
+        Ok(Some(receipt.transaction_hash))
+    }
+    
+    /// Pull `amount` from the configured payer into the signing wallet and
+    /// return the hash of the `transferFrom`. Returns `None` without sending
+    /// when no payer is configured or `amount` is zero.
+    ///
+    /// This is a standalone transaction; see `with_payer` on atomicity.
+    pub async fn fund_from_payer(&self, amount: impl Into<U256>) -> Result<Option<H256>> {
+        let amount = amount.into();
+        match self.config.payer {
+            Some(payer) if !amount.is_zero() => {
+                let call = self.token.transfer_from(payer, self.address(), amount);
+                let receipt = self.send_call(call).await?;
+                Ok(Some(receipt.transaction_hash))
+            }
+            _ => Ok(None),
+        }
+    }
+    
+    /// Approve token spending
+    pub async fn approve(&self, spender: Address, amount: impl Into<U256>) -> Result<H256> {
+        let call = self.token.approve(spender, amount.into());
+        let receipt = self.send_call(call).await?;
+        
+        Ok(receipt.transaction_hash)
+    }
+    
+    /// Sign an EIP-3009 authorization letting anyone relay a transfer from this wallet.
+    ///
+    /// The token must implement `transferWithAuthorization`; `nonce` is a
+    /// random 32-byte value that the token marks as used on submission.
+    pub async fn sign_transfer_authorization(
+        &self,
+        to: Address,
+        amount: impl Into<U256>,
+        valid_after: U256,
+        valid_before: U256,
+        nonce: [u8; 32],
+    ) -> Result<SignedAuthorization> {
+        use ethers::abi::{encode, Token};
+        use ethers::utils::keccak256;
+        
+        let value = amount.into();
+        let domain_separator = self.call_with_retry(self.token.domain_separator()).await?;
+        
+        let struct_hash = keccak256(encode(&[
+            Token::FixedBytes(keccak256(TRANSFER_WITH_AUTHORIZATION_TYPE.as_bytes()).to_vec()),
+            Token::Address(self.address()),
+            Token::Address(to),
+            Token::Uint(value),
+            Token::Uint(valid_after),
+            Token::Uint(valid_before),
+            Token::FixedBytes(nonce.to_vec()),
+        ]));
+        
+        let mut data = Vec::with_capacity(66);
+        data.extend_from_slice(b"\x19\x01");
+        data.extend_from_slice(&domain_separator);
+        data.extend_from_slice(&struct_hash);
+        
+        let signature = self.wallet.sign_hash(H256::from(keccak256(&data)))?;
+        
+        let mut r = [0u8; 32];
+        let mut s = [0u8; 32];
+        signature.r.to_big_endian(&mut r);
+        signature.s.to_big_endian(&mut s);
+        
+        Ok(SignedAuthorization {
+            from: self.address(),
+            to,
+            value,
+            valid_after,
+            valid_before,
+            nonce,
+            v: signature.v as u8,
+            r,
+            s,
+        })
+    }
+    
+    /// Submit a signed EIP-3009 authorization, paying the gas as relayer
+    pub async fn submit_transfer_authorization(&self, auth: &SignedAuthorization) -> Result<H256> {
+        let call = self.token.transfer_with_authorization(
+            auth.from,
+            auth.to,
+            auth.value,
+            auth.valid_after,
+            auth.valid_before,
+            auth.nonce,
+            auth.v,
+            auth.r,
+            auth.s,
+        );
+        let receipt = self.send_call(call).await?;
+        
+        Ok(receipt.transaction_hash)
+    }
+    
+    /// Approve all protocol contracts.
+    ///
+    /// Keeps going when an approval fails and returns the outcome per
+    /// contract, so only the failed ones need retrying.
+    pub async fn approve_all(&self) -> Vec<(Address, Result<H256>)> {
+        let max_uint = U256::MAX;
+        let mut results = Vec::new();
+        
+        for contract in self.protocol_spenders() {
+            let result = self.approve(contract, max_uint).await;
+            results.push((contract, result));
+        }
+        
+        results
+    }
+    
+    /// Protocol contracts that may pull SYNX from the wallet
+    fn protocol_spenders(&self) -> [Address; 4] {
+        [
+            self.config.contracts.payment_router,
+            self.config.contracts.reputation,
+            self.config.contracts.service_registry,
+            self.config.contracts.payment_channel,
+        ]
+    }
+    
+    /// Current allowance granted to each protocol contract
+    pub async fn protocol_allowances(&self) -> Result<Vec<(Address, U256)>> {
+        let me = self.address();
+        let spenders = self.protocol_spenders();
+        let allowances = futures::future::try_join_all(
+            spenders.iter().map(|spender| self.call_with_retry(self.token.allowance(me, *spender))),
+        )
+        .await?;
+        
+        Ok(spenders.into_iter().zip(allowances).collect())
+    }
+    
+    /// Current allowance to each protocol contract, flagging (and logging a
+    /// warning for) each one below `ALLOWANCE_TOPUP_THRESHOLD_SYNX`.
+    ///
+    /// Some token implementations decrease even `U256::MAX` approvals as
+    /// they are spent; re-approve before a payment fails on a depleted allowance.
+    pub async fn approval_remaining(&self) -> Result<Vec<AllowanceStatus>> {
+        let threshold = U256::from(ALLOWANCE_TOPUP_THRESHOLD_SYNX) * U256::exp10(18);
+        let allowances = self.protocol_allowances().await?;
+        
+        Ok(allowances
+            .into_iter()
+            .map(|(spender, allowance)| {
+                let needs_topup = allowance < threshold;
+                if needs_topup {
+                    log::warn!(
+                        "Allowance to {:?} is down to {}; re-approve before it runs out",
+                        spender,
+                        Synx::from_wei(allowance)
+                    );
+                }
+                AllowanceStatus { spender, allowance, needs_topup }
+            })
+            .collect())
+    }
+    
+    /// Approve exactly what a spend plan needs (plus its margin) instead of MAX.
+    ///
+    /// Sends one approval per spender contract touched by the plan.
+    pub async fn approve_for_plan(&self, plan: &SpendPlan) -> Result<Vec<H256>> {
+        let mut hashes = Vec::new();
+        
+        for (spender, amount) in plan.allowances(&self.config.contracts) {
+            let hash = self.approve(spender, amount).await?;
+            hashes.push(hash);
+        }
+        
+        Ok(hashes)
+    }
+    
+    /// Approve (MAX) only the contracts needed for `role`.
+    ///
+    /// Spenders whose allowance is already unlimited are skipped; returns the
+    /// hashes of the approvals actually sent.
+    pub async fn approve_for_role(&self, role: AgentRole) -> Result<Vec<H256>> {
+        let mut hashes = Vec::new();
+        
+        for spender in role.spenders(&self.config.contracts) {
+            if let Some(hash) = self.approve_if_needed(spender, U256::MAX).await? {
+                hashes.push(hash);
+            }
+        }
+        
+        Ok(hashes)
+    }
+    
+    /// Reset the allowance of `spender` to zero
+    pub async fn revoke_approval(&self, spender: Address) -> Result<H256> {
+        self.approve(spender, U256::zero()).await
+    }
+    
+    /// Flag risky allowances to the protocol contracts.
+    ///
+    /// Unlimited (`U256::MAX`) approvals are always flagged. For the router,
+    /// whose pulls can be measured from `Payment`, `EscrowCreated` and
+    /// `StreamCreated` events over the last `APPROVAL_AUDIT_WINDOW` blocks,
+    /// an allowance above `APPROVAL_EXCESS_FACTOR` times that usage is
+    /// flagged as well.
+    pub async fn audit_approvals(&self) -> Result<Vec<ApprovalAudit>> {
+        let me = H256::from(self.address());
+        let head = self.provider.provider().get_block_number().await?.as_u64();
+        let from = head.saturating_sub(APPROVAL_AUDIT_WINDOW);
+        
+        let payment_filter = self.router.event::<PaymentFilter>().topic1(me).filter;
+        let escrow_filter = self.router.event::<EscrowCreatedFilter>().topic2(me).filter;
+        let stream_filter = self.router.event::<StreamCreatedFilter>().topic2(me).filter;
+        let (allowances, payments, escrows, streams) = futures::try_join!(
+            self.protocol_allowances(),
+            self.payment_events(payment_filter, from, head),
+            self.scan_logs::<EscrowCreatedFilter>(escrow_filter, from, head),
+            self.scan_logs::<StreamCreatedFilter>(stream_filter, from, head),
+        )?;
+        
+        let router_usage = payments.iter().map(|p| p.amount)
+            .chain(escrows.iter().map(|(e, _)| e.amount))
+            .chain(streams.iter().map(|(s, _)| s.total_amount))
+            .fold(U256::zero(), |total, amount| total.saturating_add(amount));
+        
+        Ok(allowances
+            .into_iter()
+            .map(|(spender, allowance)| {
+                let recent_usage = (spender == self.config.contracts.payment_router).then_some(router_usage);
+                let excessive = recent_usage
+                    .map(|usage| allowance > usage.saturating_mul(U256::from(APPROVAL_EXCESS_FACTOR)))
+                    .unwrap_or(false);
+                ApprovalAudit {
+                    spender,
+                    allowance,
+                    recent_usage,
+                    recommend_revocation: allowance == U256::MAX || excessive,
+                }
+            })
+            .collect())
+    }
+    
+    /// Approve `amount` for `spender` unless the current allowance already covers it
+    async fn approve_if_needed(&self, spender: Address, amount: U256) -> Result<Option<H256>> {
+        let current = self.call_with_retry(self.token.allowance(self.address(), spender)).await?;
+        
+        if current >= amount {
+            return Ok(None);
+        }
+        
+        self.approve(spender, amount).await.map(Some)
+    }
+    
+    // ==================== Payment Functions ====================
+    
+    /// Send a payment
     pub async fn pay(
         &self,
         recipient: Address,
-        amount: U256,
+        amount: impl Into<U256>,
+        metadata: Option<Bytes>,
+    ) -> Result<PaymentResult> {
+        let payment_id = self.generate_payment_id("pay");
+        self.pay_with_id(payment_id, recipient, amount, metadata).await
+    }
+    
+    /// Send a payment under a caller-supplied id; the router rejects duplicates
+    pub async fn pay_with_id(
+        &self,
+        payment_id: [u8; 32],
+        recipient: Address,
+        amount: impl Into<U256>,
+        metadata: Option<Bytes>,
+    ) -> Result<PaymentResult> {
+        let amount = amount.into();
+        let meta = metadata.unwrap_or_default();
+        let funding_tx = self.fund_from_payer(amount).await?;
+        
+        let call = self.router
+            .pay(recipient, amount, payment_id, meta);
+        let receipt = self.send_call(call).await.map_err(funded(funding_tx))?;
+        
+        let fee = receipt.logs.iter()
+            .filter(|log| log.address == self.config.contracts.payment_router)
+            .filter_map(|log| parse_log::<PaymentFilter>(log.clone()).ok())
+            .find(|event| event.payment_id == payment_id)
+            .map(|event| event.fee)
+            .ok_or(SynapseError::TransactionFailed(format!("No Payment event in {:?}", receipt.transaction_hash)))?;
+        
+        Ok(PaymentResult {
+            tx_hash: receipt.transaction_hash,
+            payment_id: payment_id.into(),
+            recipient,
+            amount,
+            fee,
+        })
+    }
+    
+    /// Pay a provider-signed invoice to its provider.
+    ///
+    /// The invoice signature is checked first, `amount` must equal the
+    /// invoice total, the invoice hash is attached as payment metadata, and
+    /// the payment id is derived from the invoice id so retries are rejected
+    /// as duplicates instead of paying twice.
+    pub async fn pay_invoice(
+        &self,
+        amount: impl Into<U256>,
+        invoice: &Invoice,
+    ) -> Result<PaymentResult> {
+        let amount = amount.into();
+        if !invoice.verify() {
+            return Err(SynapseError::InvalidSignature);
+        }
+        if amount != invoice.total() {
+            return Err(SynapseError::InvalidInput(format!(
+                "Amount {} does not match invoice total {}", amount, invoice.total()
+            )));
+        }
+        
+        let payment_id = self.deterministic_id("invoice", &format!("{}-{:?}", invoice.id, invoice.provider));
+        let metadata = Bytes::from(invoice.hash().to_vec());
+        
+        self.pay_with_id(payment_id, invoice.provider, amount, Some(metadata)).await
+    }
+    
+    /// Get the router's base protocol fee in basis points
+    pub async fn fee_bps(&self) -> Result<U256> {
+        let fee = self.call_with_retry(self.router.base_fee()).await?;
+        Ok(fee)
+    }
+    
+    /// Sign a receipt for a payment this wallet received
+    pub fn sign_receipt(&self, payment: &PaymentEvent) -> Result<SignedReceipt> {
+        if payment.recipient != self.address() {
+            return Err(SynapseError::InvalidInput("Payment was not received by this wallet".to_string()));
+        }
+        
+        let mut receipt = SignedReceipt {
+            payment_id: payment.payment_id,
+            tx_hash: payment.tx_hash,
+            payer: payment.sender,
+            provider: payment.recipient,
+            amount: payment.amount,
+            signature: Bytes::default(),
+        };
+        let signature = self.wallet.sign_hash(H256::from(receipt.hash()))?;
+        receipt.signature = signature.to_vec().into();
+        
+        Ok(receipt)
+    }
+    
+    /// Check a receipt's signature and that its payment really happened.
+    ///
+    /// Returns `InvalidSignature` if the provider did not sign it, and
+    /// `PaymentNotFound` if `tx_hash` did not succeed or holds no router
+    /// `Payment` event matching the receipt's id, parties and amount.
+    pub async fn verify_receipt_onchain(&self, receipt: &SignedReceipt) -> Result<bool> {
+        if !receipt.verify() {
+            return Err(SynapseError::InvalidSignature);
+        }
+        
+        let tx_receipt = self.provider.provider()
+            .get_transaction_receipt(receipt.tx_hash)
+            .await?
+            .ok_or_else(|| SynapseError::PaymentNotFound(format!("no receipt for {:?}", receipt.tx_hash)))?;
+        if tx_receipt.status != Some(U64::one()) {
+            return Err(SynapseError::PaymentNotFound(format!("{:?} reverted", receipt.tx_hash)));
+        }
+        
+        let matched = tx_receipt.logs.into_iter()
+            .filter(|log| log.address == self.config.contracts.payment_router)
+            .filter_map(PaymentEvent::decode)
+            .any(|event| {
+                event.payment_id == receipt.payment_id
+                    && event.sender == receipt.payer
+                    && event.recipient == receipt.provider
+                    && event.amount == receipt.amount
+            });
+        if !matched {
+            return Err(SynapseError::PaymentNotFound(format!(
+                "{:?} has no matching Payment event for {:?}",
+                receipt.tx_hash, receipt.payment_id
+            )));
+        }
+        
+        Ok(true)
+    }
+    
+    /// Sign that the result with `result_hash` (see `delivery_result_hash`)
+    /// was delivered for `payment_id`
+    pub fn sign_delivery_proof(&self, payment_id: [u8; 32], result_hash: [u8; 32]) -> Result<DeliveryProof> {
+        let mut proof = DeliveryProof {
+            payment_id: payment_id.into(),
+            result_hash: result_hash.into(),
+            provider: self.address(),
+            signature: Bytes::default(),
+        };
+        proof.sign(&self.wallet)?;
+        
+        Ok(proof)
+    }
+    
+    /// True if `proof` was signed by the provider it names. Arbiters should
+    /// also check that provider against the escrow recipient.
+    pub fn verify_delivery_proof(&self, proof: &DeliveryProof) -> bool {
+        proof.verify()
+    }
+    
+    /// Check that each payment landed: its receipt succeeded and holds a
+    /// router `Payment` event with the expected id, recipient and amount.
+    ///
+    /// Receipts are fetched concurrently.
+    pub async fn verify_payments(&self, results: &[PaymentResult]) -> Result<Vec<PaymentVerification>> {
+        let provider = self.provider.provider();
+        let receipts = futures::future::try_join_all(
+            results.iter().map(|result| provider.get_transaction_receipt(result.tx_hash)),
+        )
+        .await?;
+        
+        Ok(results.iter()
+            .zip(receipts)
+            .map(|(result, receipt)| {
+                let status = match receipt {
+                    None => PaymentStatus::NotFound,
+                    Some(receipt) if receipt.status != Some(U64::one()) => PaymentStatus::Reverted,
+                    Some(receipt) => {
+                        let matched = receipt.logs.into_iter()
+                            .filter(|log| log.address == self.config.contracts.payment_router)
+                            .filter_map(PaymentEvent::decode)
+                            .any(|event| {
+                                event.payment_id == result.payment_id
+                                    && event.recipient == result.recipient
+                                    && event.amount == result.amount
+                            });
+                        if matched { PaymentStatus::Confirmed } else { PaymentStatus::NotFound }
+                    }
+                };
+                PaymentVerification { payment_id: result.payment_id, tx_hash: result.tx_hash, status }
+            })
+            .collect())
+    }
+    
+    /// Whether a sent payment is still part of the canonical chain with at
+    /// least `required_confirmations`.
+    ///
+    /// Returns `false` if the transaction vanished, reverted, was re-mined
+    /// in a different block, or has too few confirmations.
+    pub async fn confirm_still_valid(&self, payment: &PaymentResult, required_confirmations: u64) -> Result<bool> {
+        let provider = self.provider.provider();
+        let receipt = match provider.get_transaction_receipt(payment.tx_hash).await? {
+            Some(receipt) => receipt,
+            None => return Ok(false),
+        };
+        if receipt.status != Some(U64::one()) {
+            return Ok(false);
+        }
+        
+        let (block_number, block_hash) = match (receipt.block_number, receipt.block_hash) {
+            (Some(number), Some(hash)) => (number, hash),
+            _ => return Ok(false),
+        };
+        let (head, canonical) = futures::try_join!(
+            provider.get_block_number(),
+            provider.get_block(block_number),
+        )?;
+        if canonical.and_then(|block| block.hash) != Some(block_hash) {
+            return Ok(false);
+        }
+        
+        let confirmations = head.saturating_sub(block_number).as_u64() + 1;
+        Ok(confirmations >= required_confirmations)
+    }
+    
+    /// Fee discount in basis points the router grants `agent` for its current tier
+    pub async fn fee_discount_bps(&self, agent: Address) -> Result<U256> {
+        let tier = self.call_with_retry(self.reputation.get_tier(agent)).await?;
+        let discount = self.call_with_retry(self.router.tier_discounts(tier)).await?;
+        Ok(discount)
+    }
+    
+    /// Pay so that `recipient` receives exactly `net` after the protocol fee.
+    ///
+    /// The gross amount is derived from the base fee and this wallet's tier
+    /// discount. The `Payment` event is then checked, and if the delivered
+    /// amount differs from `net` by more than `NET_PAYMENT_TOLERANCE` wei
+    /// (e.g. the fee changed in between) `NetAmountMismatch` is returned;
+    /// the payment itself has been made at that point.
+    pub async fn pay_exact_net(
+        &self,
+        recipient: Address,
+        net: impl Into<U256>,
+        metadata: Option<Bytes>,
+    ) -> Result<PaymentResult> {
+        let net = net.into();
+        let (fee_bps, discount_bps) = futures::try_join!(
+            self.fee_bps(),
+            self.fee_discount_bps(self.address()),
+        )?;
+        let gross = gross_for_net(net, fee_bps, discount_bps)
+            .ok_or(SynapseError::InvalidInput(format!("No gross amount delivers {} at a fee of {} bps", net, fee_bps)))?;
+        
+        let result = self.pay(recipient, gross, metadata).await?;
+        
+        let received = result.amount.saturating_sub(result.fee);
+        let difference = if received > net { received - net } else { net - received };
+        if difference > U256::from(NET_PAYMENT_TOLERANCE) {
+            return Err(SynapseError::NetAmountMismatch { expected: net, received });
+        }
+        
+        Ok(result)
+    }
+    
+    /// Estimate the protocol fee for each amount of a batch payout.
+    ///
+    /// Uses the base fee only, so tier discounts make the real fees equal or lower.
+    pub async fn estimate_batch_fees(&self, amounts: &[U256]) -> Result<Vec<U256>> {
+        let fee_bps = self.fee_bps().await?;
+        Ok(amounts.iter().map(|amount| protocol_fee(*amount, fee_bps)).collect())
+    }
+    
+    /// Stream `Payment` events after `checkpoint`: first backfills from the
+    /// checkpoint block to the chain head, then continues with live events.
+    ///
+    /// Persist `PaymentEvent::checkpoint()` of each processed event to resume
+    /// after a restart without gaps or duplicates.
+    pub async fn watch_payments_from(
+        &self,
+        checkpoint: EventCheckpoint,
+    ) -> Result<impl Stream<Item = PaymentEvent> + '_> {
+        let filter = self.router.event::<PaymentFilter>().filter;
+        
+        // Install the live filter before backfilling so nothing falls in between
+        let watcher = self.provider.provider().watch(&filter).await?;
+        let head = self.provider.provider().get_block_number().await?.as_u64();
+        
+        let backfill: Vec<PaymentEvent> = self
+            .scan_logs::<PaymentFilter>(filter, checkpoint.block, head)
+            .await?
+            .into_iter()
+            .map(|(event, meta)| PaymentEvent::from_log(event, meta))
+            .filter(|event| event.checkpoint() > checkpoint)
+            .collect();
+        
+        let floor = backfill.last().map(|e| e.checkpoint()).unwrap_or(checkpoint);
+        
+        let live = watcher.filter_map(move |log| async move {
+            PaymentEvent::decode(log).filter(|event| event.checkpoint() > floor)
+        });
+        
+        Ok(futures::stream::iter(backfill).chain(live))
+    }
+    
+    /// Wait until `from` pays this wallet at least `min_amount` (net of fees).
+    ///
+    /// Only payments made after the call are observed; fails with
+    /// `SynapseError::Timeout` if none arrives within `timeout`.
+    pub async fn await_payment(
+        &self,
+        from: Address,
+        min_amount: U256,
+        timeout: Duration,
+    ) -> Result<PaymentEvent> {
+        let filter = self.router.event::<PaymentFilter>()
+            .topic1(H256::from(from))
+            .topic2(H256::from(self.address()))
+            .filter;
+        let watcher = self.provider.provider().watch(&filter).await?;
+        
+        let mut payments = Box::pin(watcher.filter_map(move |log| async move {
+            PaymentEvent::decode(log).filter(|event| event.net_amount() >= min_amount)
+        }));
+        
+        tokio::time::timeout(timeout, payments.next())
+            .await
+            .map_err(|_| SynapseError::Timeout(format!("No payment from {:?} within {:?}", from, timeout)))?
+            .ok_or(SynapseError::Timeout("Payment watcher closed".to_string()))
+    }
+    
+    /// Flag parts of a batch payout an adversary could exploit by reordering
+    /// or sandwiching it: AMM pool recipients, other contract recipients and
+    /// repeated recipients. A heuristic advisory, not a guarantee.
+    pub async fn analyze_batch_mev(&self, recipients: &[Address], amounts: &[U256]) -> Result<MevReport> {
+        if recipients.len() != amounts.len() {
+            return Err(SynapseError::InvalidInput("Recipients and amounts differ in length".to_string()));
+        }
+        
+        let mut unique: Vec<Address> = Vec::new();
+        let mut report = MevReport::default();
+        for recipient in recipients {
+            if unique.contains(recipient) {
+                if !report.duplicate_recipients.contains(recipient) {
+                    report.duplicate_recipients.push(*recipient);
+                }
+            } else {
+                unique.push(*recipient);
+            }
+        }
+        
+        let provider = self.provider.provider();
+        let codes = futures::future::try_join_all(unique.iter().map(|r| provider.get_code(*r, None))).await?;
+        report.contract_recipients = unique.into_iter()
+            .zip(codes)
+            .filter(|(_, code)| !code.is_empty())
+            .map(|(recipient, _)| recipient)
+            .collect();
+        
+        for contract in &report.contract_recipients {
+            for selector in AMM_POOL_PROBES {
+                let probe: TypedTransaction = TransactionRequest::new().to(*contract).data(selector.to_vec()).into();
+                if matches!(provider.call(&probe, None).await, Ok(data) if data.len() >= 64) {
+                    report.amm_recipients.push(*contract);
+                    break;
+                }
+            }
+        }
+        
+        report.amm_exposure = recipients.iter()
+            .zip(amounts)
+            .filter(|(recipient, _)| report.amm_recipients.contains(recipient))
+            .fold(U256::zero(), |total, (_, amount)| total.saturating_add(*amount));
+        
+        Ok(report)
+    }
+    
+    /// Build and sign router payments with nonces counting up from
+    /// `starting_nonce`, without touching the network.
+    ///
+    /// Meant for an air-gapped signer; hand the result to `broadcast_signed`
+    /// on an online machine. The payer funding path is not applied.
+    pub fn sign_payments_offline(&self, payments: Vec<OfflinePayment>, starting_nonce: U256) -> Result<Vec<SignedTransaction>> {
+        use ethers::utils::keccak256;
+        
+        payments
+            .into_iter()
+            .enumerate()
+            .map(|(i, spec)| {
+                let nonce = starting_nonce + i;
+                let payment_id = self.generate_payment_id(&format!("offline-{}", nonce));
+                let data = self.router
+                    .pay(spec.recipient, spec.amount, payment_id, spec.metadata)
+                    .calldata()
+                    .ok_or(SynapseError::ContractError("Failed to encode pay calldata".to_string()))?;
+                
+                let mut tx: TypedTransaction = match self.config.gas_model {
+                    GasModel::Legacy => TransactionRequest::new()
+                        .gas_price(spec.max_fee_per_gas)
+                        .into(),
+                    GasModel::Eip1559 => Eip1559TransactionRequest::new()
+                        .max_fee_per_gas(spec.max_fee_per_gas)
+                        .max_priority_fee_per_gas(spec.max_priority_fee_per_gas)
+                        .into(),
+                };
+                tx.set_from(self.address());
+                tx.set_to(self.config.contracts.payment_router);
+                tx.set_data(data);
+                tx.set_nonce(nonce);
+                tx.set_gas(spec.gas_limit);
+                tx.set_chain_id(self.config.chain_id);
+                
+                let signature = self.wallet.sign_transaction_sync(&tx)?;
+                let raw = tx.rlp_signed(&signature);
+                
+                Ok(SignedTransaction {
+                    nonce,
+                    payment_id: payment_id.into(),
+                    tx_hash: H256::from(keccak256(&raw)),
+                    raw,
+                })
+            })
+            .collect()
+    }
+    
+    /// Broadcast transactions signed by `sign_payments_offline` in nonce
+    /// order and wait for the configured confirmations.
+    ///
+    /// Returns `(payment_id, result)` per transaction in nonce order; a
+    /// failed broadcast does not stop the rest, though later nonces may then
+    /// stay pending until the gap is filled.
+    pub async fn broadcast_signed(&self, transactions: &[SignedTransaction]) -> Vec<(H256, Result<H256>)> {
+        let mut ordered: Vec<&SignedTransaction> = transactions.iter().collect();
+        ordered.sort_by_key(|tx| tx.nonce);
+        
+        let provider = self.provider.provider();
+        let mut sent = Vec::with_capacity(ordered.len());
+        for tx in ordered {
+            let hash = provider.send_raw_transaction(tx.raw.clone()).await
+                .map(|pending| pending.tx_hash())
+                .map_err(SynapseError::from);
+            sent.push((tx.payment_id, hash));
+        }
+        // Nonces were consumed outside the client's tracking
+        self.invalidate_nonce().await;
+        
+        futures::future::join_all(sent.into_iter().map(|(payment_id, hash)| async move {
+            let confirmed = match hash {
+                Ok(hash) => self.wait_for_receipt(hash).await.map(|receipt| receipt.transaction_hash),
+                Err(e) => Err(e),
+            };
+            (payment_id, confirmed)
+        }))
+        .await
+    }
+    
+    /// Send batch payments
+    pub async fn batch_pay(
+        &self,
+        recipients: Vec<Address>,
+        amounts: Vec<U256>,
+    ) -> Result<H256> {
+        let payment_ids: Vec<[u8; 32]> = recipients
+            .iter()
+            .enumerate()
+            .map(|(i, _)| self.generate_payment_id(&format!("batch-{}", i)))
+            .collect();
+        
+        let metadata: Vec<Bytes> = vec![Bytes::default(); recipients.len()];
+        
+        let total = amounts.iter().fold(U256::zero(), |total, amount| total.saturating_add(*amount));
+        let funding_tx = self.fund_from_payer(total).await?;
+        
+        let call = self.router
+            .batch_pay(recipients, amounts, payment_ids, metadata);
+        let receipt = self.send_call(call).await.map_err(funded(funding_tx))?;
+        
+        Ok(receipt.transaction_hash)
+    }
+    
+    /// `Payment` events in `[from_block, to_block]` matching `filter`
+    async fn payment_events(&self, filter: Filter, from_block: u64, to_block: u64) -> Result<Vec<PaymentEvent>> {
+        Ok(self
+            .scan_logs::<PaymentFilter>(filter, from_block, to_block)
+            .await?
+            .into_iter()
+            .map(|(event, meta)| PaymentEvent::from_log(event, meta))
+            .collect())
+    }
+    
+    /// Fee recorded for a past payment, searching `Payment` events from `from_block`.
+    ///
+    /// `paymentId` is not an indexed topic, so every `Payment` log in the
+    /// range is scanned; pass the earliest block the payment can be in.
+    pub async fn get_payment_fee(&self, payment_id: [u8; 32], from_block: u64) -> Result<U256> {
+        let filter = self.router.event::<PaymentFilter>().filter;
+        let head = self.provider.provider().get_block_number().await?.as_u64();
+        
+        self.payment_events(filter, from_block, head)
+            .await?
+            .into_iter()
+            .find(|event| event.payment_id == H256::from(payment_id))
+            .map(|event| event.fee)
+            .ok_or_else(|| SynapseError::PaymentNotFound(format!("0x{}", hex::encode(payment_id))))
+    }
+    
+    /// Estimate the SYNX and native balance needed to keep sending
+    /// `expected_tx_per_day` payments for `days`.
+    ///
+    /// Averages are measured from this wallet's payments over the last
+    /// `RUNWAY_SAMPLE_BLOCKS` (gas from the receipts of the most recent
+    /// ones). Without history, gas is `PAY_GAS` at the current price.
+    pub async fn runway_balance(&self, expected_tx_per_day: u64, days: u64) -> Result<RunwayEstimate> {
+        let provider = self.provider.provider();
+        let head = provider.get_block_number().await?.as_u64();
+        let filter = self.router.event::<PaymentFilter>().topic1(H256::from(self.address())).filter;
+        let payments = self.payment_events(filter, head.saturating_sub(RUNWAY_SAMPLE_BLOCKS), head).await?;
+        
+        let recent = &payments[payments.len().saturating_sub(RUNWAY_SAMPLE_RECEIPTS)..];
+        let receipts = futures::future::try_join_all(
+            recent.iter().map(|payment| provider.get_transaction_receipt(payment.tx_hash)),
+        )
+        .await?;
+        let gas_costs: Vec<U256> = receipts.into_iter()
+            .flatten()
+            .filter_map(|receipt| Some(receipt.gas_used? * receipt.effective_gas_price?))
+            .collect();
+        
+        let gas_cost_per_tx = if gas_costs.is_empty() {
+            provider.get_gas_price().await? * U256::from(PAY_GAS)
+        } else {
+            gas_costs.iter().fold(U256::zero(), |total, cost| total + cost) / U256::from(gas_costs.len())
+        };
+        let synx_per_tx = if payments.is_empty() {
+            U256::zero()
+        } else {
+            payments.iter().fold(U256::zero(), |total, p| total.saturating_add(p.amount)) / U256::from(payments.len())
+        };
+        
+        let transactions = expected_tx_per_day.saturating_mul(days);
+        Ok(RunwayEstimate {
+            transactions,
+            synx_per_tx,
+            gas_cost_per_tx,
+            synx_needed: synx_per_tx.saturating_mul(U256::from(transactions)),
+            native_needed: gas_cost_per_tx.saturating_mul(U256::from(transactions)),
+            sampled_payments: payments.len(),
+        })
+    }
+    
+    /// Directed payment graph reachable from `seed_agents` by following
+    /// outgoing `Payment` events since `from_block`.
+    ///
+    /// Depth 1 scans the seeds' payments, depth 2 also their payees', and
+    /// so on. `depth` is capped at `TRUST_GRAPH_MAX_DEPTH` and expansion stops
+    /// once `TRUST_GRAPH_MAX_AGENTS` agents have been scanned; each scan
+    /// covers `[from_block, head]`, so keep the range recent on busy chains.
+    pub async fn trust_graph(&self, seed_agents: &[Address], depth: u8, from_block: u64) -> Result<TrustGraph> {
+        let head = self.provider.provider().get_block_number().await?.as_u64();
+        
+        let mut graph = TrustGraph::default();
+        let mut edges: HashMap<(Address, Address), (U256, u64)> = HashMap::new();
+        let mut frontier: Vec<Address> = Vec::new();
+        for seed in seed_agents {
+            if !graph.nodes.contains(seed) {
+                graph.nodes.push(*seed);
+                frontier.push(*seed);
+            }
+        }
+        
+        let mut scanned = 0;
+        for _ in 0..depth.min(TRUST_GRAPH_MAX_DEPTH) {
+            frontier.truncate(TRUST_GRAPH_MAX_AGENTS.saturating_sub(scanned));
+            if frontier.is_empty() {
+                break;
+            }
+            scanned += frontier.len();
+            
+            let payments = futures::future::try_join_all(frontier.iter().map(|agent| {
+                let filter = self.router.event::<PaymentFilter>().topic1(H256::from(*agent)).filter;
+                self.payment_events(filter, from_block, head)
+            }))
+            .await?;
+            
+            let mut next = Vec::new();
+            for payment in payments.into_iter().flatten() {
+                let edge = edges.entry((payment.sender, payment.recipient)).or_insert((U256::zero(), 0));
+                edge.0 = edge.0.saturating_add(payment.amount);
+                edge.1 += 1;
+                
+                if !graph.nodes.contains(&payment.recipient) {
+                    graph.nodes.push(payment.recipient);
+                    next.push(payment.recipient);
+                }
+            }
+            frontier = next;
+        }
+        
+        graph.edges = edges.into_iter()
+            .map(|((from, to), (volume, count))| TrustEdge { from, to, volume, count })
+            .collect();
+        graph.edges.sort_by_key(|edge| std::cmp::Reverse(edge.volume));
+        
+        Ok(graph)
+    }
+    
+    /// Aggregate the `ComputeReceipt` metadata of this wallet's payments
+    /// `payment_ids`, sent since `from_block`.
+    ///
+    /// Metadata is not part of the `Payment` event, so it is decoded from
+    /// the calldata of the `pay` / `batchPay` transaction carrying each payment.
+    pub async fn sum_compute(&self, payment_ids: &[[u8; 32]], from_block: u64) -> Result<ComputeSummary> {
+        let provider = self.provider.provider();
+        let head = provider.get_block_number().await?.as_u64();
+        let filter = self.router.event::<PaymentFilter>().topic1(H256::from(self.address())).filter;
+        let events: Vec<PaymentEvent> = self.payment_events(filter, from_block, head)
+            .await?
+            .into_iter()
+            .filter(|event| payment_ids.contains(&event.payment_id.0))
+            .collect();
+        
+        let transactions = futures::future::try_join_all(
+            events.iter().map(|event| provider.get_transaction(event.tx_hash)),
+        )
+        .await?;
+        
+        let mut summary = ComputeSummary::default();
+        let mut found = Vec::new();
+        for (event, tx) in events.iter().zip(transactions) {
+            let metadata = tx.and_then(|tx| payment_metadata(event, &tx.input));
+            
+            if let Some(receipt) = metadata.and_then(|m| ComputeReceipt::decode(&m).ok()) {
+                summary.payments += 1;
+                summary.total_energy_wh += receipt.energy_wh;
+                summary.total_duration_ms = summary.total_duration_ms.saturating_add(receipt.duration_ms);
+                *summary.energy_by_model.entry(receipt.model).or_insert(0.0) += receipt.energy_wh;
+                found.push(event.payment_id.0);
+            }
+        }
+        summary.missing = payment_ids.iter().filter(|id| !found.contains(id)).copied().collect();
+        
+        Ok(summary)
+    }
+    
+    /// Payments sent or received by this wallet, ordered by block and log index
+    pub async fn my_payment_history(&self, from_block: u64, to_block: u64) -> Result<Vec<PaymentEvent>> {
+        let me = H256::from(self.address());
+        let sent_filter = self.router.event::<PaymentFilter>().topic1(me).filter;
+        let received_filter = self.router.event::<PaymentFilter>().topic2(me).filter;
+        
+        let (sent, received) = futures::try_join!(
+            self.payment_events(sent_filter, from_block, to_block),
+            self.payment_events(received_filter, from_block, to_block),
+        )?;
+        
+        let mut history: Vec<PaymentEvent> = sent.into_iter().chain(received).collect();
+        history.sort_by_key(|event| event.checkpoint());
+        history.dedup_by_key(|event| event.checkpoint());
+        
+        Ok(history)
+    }
+    
+    /// Net payment flow with `counterparty` over `[from_block, to_block]`.
+    ///
+    /// Returns `sent - received` in gross payment amounts: a positive value
+    /// means this wallet paid more and the counterparty owes it the
+    /// difference; a negative value means this wallet owes the counterparty.
+    pub async fn net_settlement(
+        &self,
+        counterparty: Address,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<I256> {
+        let me = H256::from(self.address());
+        let them = H256::from(counterparty);
+        let sent_filter = self.router.event::<PaymentFilter>().topic1(me).topic2(them).filter;
+        let received_filter = self.router.event::<PaymentFilter>().topic1(them).topic2(me).filter;
+        
+        let (sent, received) = futures::try_join!(
+            self.payment_events(sent_filter, from_block, to_block),
+            self.payment_events(received_filter, from_block, to_block),
+        )?;
+        
+        let sum = |events: &[PaymentEvent]| -> Result<I256> {
+            let total = events.iter().fold(U256::zero(), |total, e| total.saturating_add(e.amount));
+            I256::try_from(total).map_err(|e| SynapseError::ContractError(e.to_string()))
+        };
+        
+        Ok(sum(&sent)? - sum(&received)?)
+    }
+    
+    /// Reconcile the `Payment` events of a batch transaction against the
+    /// intended `(recipient, amount)` manifest
+    pub async fn verify_batch_payout(
+        &self,
+        tx_hash: H256,
+        expected: &[(Address, U256)],
+    ) -> Result<BatchAudit> {
+        let receipt = self.provider.provider().get_transaction_receipt(tx_hash).await?
+            .ok_or(SynapseError::TransactionFailed(format!("No receipt for {:?}", tx_hash)))?;
+        
+        let actual: Vec<(Address, U256)> = receipt.logs.into_iter()
+            .filter(|log| log.address == self.config.contracts.payment_router)
+            .filter_map(|log| parse_log::<PaymentFilter>(log).ok())
+            .map(|event| (event.recipient, event.amount))
+            .collect();
+        
+        Ok(BatchAudit::compare(expected, &actual))
+    }
+    
+    /// Create an escrow
+    pub async fn create_escrow(
+        &self,
+        recipient: Address,
+        arbiter: Address,
+        amount: impl Into<U256>,
+        deadline: U256,
+    ) -> Result<H256> {
+        let escrow_id = self.generate_payment_id("escrow");
+        self.create_escrow_with_id(escrow_id, recipient, arbiter, amount, deadline).await
+    }
+    
+    /// Create an escrow under a caller-supplied id.
+    ///
+    /// Retrying with the same id is safe: the router rejects a duplicate
+    /// escrow id instead of locking the funds twice.
+    pub async fn create_escrow_with_id(
+        &self,
+        escrow_id: [u8; 32],
+        recipient: Address,
+        arbiter: Address,
+        amount: impl Into<U256>,
+        deadline: U256,
+    ) -> Result<H256> {
+        let amount = amount.into();
+        let funding_tx = self.fund_from_payer(amount).await?;
+        
+        let call = self.router
+            .create_escrow(recipient, arbiter, amount, deadline, escrow_id.into(), Bytes::default());
+        let receipt = self.send_call(call).await.map_err(funded(funding_tx))?;
+        
+        Ok(receipt.transaction_hash)
+    }
+    
+    /// Get escrow information
+    pub async fn get_escrow(&self, escrow_id: [u8; 32]) -> Result<EscrowInfo> {
+        let escrow = self.call_with_retry(self.router.escrows(escrow_id)).await?;
+        
+        if escrow.0 == Address::zero() {
+            return Err(SynapseError::EscrowNotFound);
+        }
+        
+        Ok(EscrowInfo {
+            escrow_id: escrow_id.into(),
+            sender: escrow.0,
+            recipient: escrow.1,
+            arbiter: escrow.2,
+            amount: escrow.3,
+            deadline: escrow.4,
+            status: EscrowStatus::from(escrow.5),
+        })
+    }
+    
+    /// Refund every escrow among `escrow_ids` that is still `Open` and whose
+    /// deadline has passed, pipelined. Refunds always go to the escrow's sender.
+    ///
+    /// Returns `(escrow_id, result)` per refund sent and per escrow that
+    /// could not be read (e.g. `EscrowNotFound`), in the order of
+    /// `escrow_ids`; other escrows are skipped. One failed read or refund
+    /// does not affect the others.
+    pub async fn refund_expired_escrows(&self, escrow_ids: &[[u8; 32]]) -> Result<Vec<([u8; 32], Result<H256>)>> {
+        let (escrows, now) = futures::join!(
+            futures::future::join_all(escrow_ids.iter().map(|id| self.get_escrow(*id))),
+            self.chain_time(),
+        );
+        let now = now?;
+        
+        // `None` marks an escrow to refund, `Some` a failed read
+        let mut selected: Vec<([u8; 32], Option<SynapseError>)> = Vec::new();
+        for (id, escrow) in escrow_ids.iter().zip(escrows) {
+            match escrow {
+                Ok(escrow) if escrow.status == EscrowStatus::Open && escrow.deadline <= now => selected.push((*id, None)),
+                Ok(_) => {}
+                Err(e) => selected.push((*id, Some(e))),
+            }
+        }
+        
+        let calls = selected.iter()
+            .filter(|(_, error)| error.is_none())
+            .map(|(id, _)| self.router.refund_escrow(*id))
+            .collect();
+        let mut sent = self.send_calls_pipelined(calls).await.into_iter();
+        
+        Ok(selected
+            .into_iter()
+            .map(|(id, error)| {
+                let outcome = match error {
+                    Some(e) => Err(e),
+                    None => sent.next()
+                        .unwrap_or_else(|| Err(SynapseError::TransactionFailed("Refund was not sent".to_string())))
+                        .map(|receipt| receipt.transaction_hash),
+                };
+                (id, outcome)
+            })
+            .collect())
+    }
+    
+    /// Escrows among `escrow_ids` that exist, in order; ids failing with
+    /// `EscrowNotFound` are dropped and any other read error is returned
+    async fn existing_escrows(&self, escrow_ids: &[[u8; 32]]) -> Result<Vec<EscrowInfo>> {
+        let escrows = futures::future::join_all(escrow_ids.iter().map(|id| self.get_escrow(*id))).await;
+        
+        let mut existing = Vec::with_capacity(escrows.len());
+        for (id, escrow) in escrow_ids.iter().zip(escrows) {
+            match escrow {
+                Ok(escrow) => existing.push(escrow),
+                Err(SynapseError::EscrowNotFound) => log::debug!("Skipping unknown escrow 0x{}", hex::encode(id)),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(existing)
+    }
+    
+    /// Total amount still locked in the `Open` escrows among `escrow_ids`;
+    /// unknown ids count as zero
+    pub async fn escrow_exposure(&self, escrow_ids: &[[u8; 32]]) -> Result<U256> {
+        let escrows = self.existing_escrows(escrow_ids).await?;
+        
+        Ok(escrows
+            .iter()
+            .filter(|escrow| escrow.status == EscrowStatus::Open)
+            .fold(U256::zero(), |total, escrow| total.saturating_add(escrow.amount)))
+    }
+    
+    /// Escrows among `escrow_ids` arbitrated by this wallet, nearest
+    /// deadline first; unknown ids are left off the worklist
+    pub async fn arbiter_dashboard(&self, escrow_ids: &[[u8; 32]]) -> Result<Vec<ArbiterEscrow>> {
+        let (escrows, now) = futures::try_join!(
+            self.existing_escrows(escrow_ids),
+            self.chain_time(),
+        )?;
+        
+        let mut worklist: Vec<ArbiterEscrow> = escrows.into_iter()
+            .filter(|escrow| escrow.arbiter == self.address())
+            .map(|escrow| ArbiterEscrow {
+                time_to_deadline: Duration::from_secs(escrow.deadline.saturating_sub(now).low_u64()),
+                escrow,
+            })
+            .collect();
+        worklist.sort_by_key(|entry| entry.escrow.deadline);
+        
+        Ok(worklist)
+    }
+    
+    /// Suggested deadline timestamp for an escrow paying `service_id`; see
+    /// `escrow_deadline_window`.
+    ///
+    /// Fulfillment time is measured for the service's recent `ServiceRequest`
+    /// events as the delay until the requester's next `Payment` to the
+    /// provider for this service, over the last `ESCROW_HISTORY_BLOCKS`. A
+    /// payment counts only if its metadata is `TipMetadata` naming
+    /// `service_id` (as sent by `pay_with_tip`), and settles one request at
+    /// most. Without such payments the per-model default is used.
+    pub async fn recommend_escrow_deadline(&self, service_id: [u8; 32]) -> Result<U256> {
+        let provider = self.provider.provider();
+        let (service, head, now) = futures::try_join!(
+            self.get_service(service_id),
+            async { provider.get_block_number().await.map_err(SynapseError::from) },
+            self.chain_time(),
+        )?;
+        let head = head.as_u64();
+        let from = head.saturating_sub(ESCROW_HISTORY_BLOCKS);
+        
+        let request_filter = self.services.event::<ServiceRequestFilter>()
+            .topic1(H256::from(service_id))
+            .filter;
+        let payment_filter = self.router.event::<PaymentFilter>()
+            .topic2(H256::from(service.provider))
+            .filter;
+        let (requests, payments) = futures::try_join!(
+            self.scan_logs::<ServiceRequestFilter>(request_filter, from, head),
+            self.payment_events(payment_filter, from, head),
+        )?;
+        
+        let recent = &requests[requests.len().saturating_sub(ESCROW_SAMPLE_REQUESTS)..];
+        let candidates: Vec<&PaymentEvent> = payments.iter()
+            .filter(|p| recent.iter().any(|(request, _)| request.requester == p.sender))
+            .collect();
+        let transactions = futures::future::try_join_all(
+            candidates.iter().map(|p| provider.get_transaction(p.tx_hash)),
+        )
+        .await?;
+        let mut for_service: Vec<&PaymentEvent> = candidates.into_iter()
+            .zip(transactions)
+            .filter(|(p, tx)| {
+                tx.as_ref()
+                    .and_then(|tx| payment_metadata(p, &tx.input))
+                    .and_then(|metadata| TipMetadata::decode(&metadata).ok())
+                    .is_some_and(|tip| tip.service_id == H256::from(service_id))
+            })
+            .map(|(p, _)| p)
+            .collect();
+        
+        let mut pairs: Vec<(u64, u64)> = Vec::new();
+        for (request, meta) in recent {
+            let requested = meta.block_number.as_u64();
+            if let Some(i) = for_service.iter().position(|p| p.sender == request.requester && p.block_number >= requested) {
+                pairs.push((requested, for_service.remove(i).block_number));
+            }
+        }
+        
+        let mut blocks: Vec<u64> = pairs.iter().flat_map(|(a, b)| [*a, *b]).collect();
+        blocks.sort_unstable();
+        blocks.dedup();
+        let timestamps: HashMap<u64, U256> = futures::future::try_join_all(blocks.iter().map(|n| provider.get_block(*n)))
+            .await?
+            .into_iter()
+            .flatten()
+            .filter_map(|block| Some((block.number?.as_u64(), block.timestamp)))
+            .collect();
+        
+        let fulfillment_secs: Vec<u64> = pairs.iter()
+            .filter_map(|(requested, paid)| {
+                Some(timestamps.get(paid)?.saturating_sub(*timestamps.get(requested)?).low_u64())
+            })
+            .collect();
+        
+        let window = escrow_deadline_window(service.pricing_model, &fulfillment_secs);
+        Ok(now + U256::from(window))
+    }
+    
+    /// List open escrows where this wallet is the recipient.
+    ///
+    /// Scans `EscrowCreated` from the configured deployment block, then reads
+    /// each escrow's current state.
+    pub async fn my_pending_escrows(&self) -> Result<Vec<EscrowInfo>> {
+        let filter = self.router.event::<EscrowCreatedFilter>()
+            .topic3(H256::from(self.address()))
+            .filter;
+        let head = self.provider.provider().get_block_number().await?.as_u64();
+        
+        let created = self
+            .scan_logs::<EscrowCreatedFilter>(filter, self.config.deployment_block, head)
+            .await?;
+        
+        let escrows = futures::future::try_join_all(
+            created.iter().map(|(event, _)| self.get_escrow(event.escrow_id)),
+        )
+        .await?;
+        
+        Ok(escrows
+            .into_iter()
+            .filter(|escrow| escrow.status == EscrowStatus::Open)
+            .collect())
+    }
+    
+    /// Create a payment stream
+    pub async fn create_stream(
+        &self,
+        recipient: Address,
+        total_amount: impl Into<U256>,
+        start_time: U256,
+        end_time: U256,
+    ) -> Result<StreamResult> {
+        let stream_id = self.generate_payment_id("stream");
+        self.create_stream_with_id(stream_id, recipient, total_amount, start_time, end_time).await
+    }
+    
+    /// Create a payment stream under a caller-supplied id (see `stream_id_for`)
+    pub async fn create_stream_with_id(
+        &self,
+        stream_id: [u8; 32],
+        recipient: Address,
+        total_amount: impl Into<U256>,
+        start_time: U256,
+        end_time: U256,
+    ) -> Result<StreamResult> {
+        let total_amount = total_amount.into();
+        let now = self.chain_time().await?;
+        validate_stream_times(start_time, end_time, now, self.config.max_stream_backdate)?;
+        let funding_tx = self.fund_from_payer(total_amount).await?;
+        
+        let call = self.router
+            .create_stream(recipient, total_amount, start_time, end_time, stream_id.into());
+        let receipt = self.send_call(call).await.map_err(funded(funding_tx))?;
+        
+        Ok(StreamResult {
+            tx_hash: receipt.transaction_hash,
+            stream_id: stream_id.into(),
+            total_amount,
+            start_time,
+            end_time,
+        })
+    }
+    
+    /// Create several streams, sent back to back with sequential nonces.
+    ///
+    /// Stream ids are taken from the `StreamCreated` events. If any stream
+    /// fails to send or confirm, `PartialStreamFailure` carries both the
+    /// streams that were created and the reason for each failure (wrapped in
+    /// `FundedSpendFailed` when the total was pulled from a payer).
+    pub async fn create_streams(&self, streams: Vec<StreamSpec>) -> Result<Vec<StreamResult>> {
+        let now = self.chain_time().await?;
+        for spec in &streams {
+            validate_stream_times(spec.start_time, spec.end_time, now, self.config.max_stream_backdate)?;
+        }
+        
+        let total = streams.iter()
+            .fold(U256::zero(), |total, spec| total.saturating_add(spec.total_amount));
+        let funding_tx = self.fund_from_payer(total).await?;
+        
+        let mut submitted = Vec::with_capacity(streams.len());
+        for spec in &streams {
+            let stream_id = self.generate_payment_id("stream");
+            let call = self.router
+                .create_stream(spec.recipient, spec.total_amount, spec.start_time, spec.end_time, stream_id);
+            submitted.push(self.submit_call(call).await);
+        }
+        
+        let outcomes = futures::future::join_all(submitted.into_iter().map(|hash| async move {
+            let receipt = self.wait_for_receipt(hash?).await?;
+            receipt.logs.iter()
+                .filter(|log| log.address == self.config.contracts.payment_router)
+                .find_map(|log| parse_log::<StreamCreatedFilter>(log.clone()).ok())
+                .map(|event| (receipt.transaction_hash, H256::from(event.stream_id)))
+                .ok_or(SynapseError::TransactionFailed(format!(
+                    "No StreamCreated event in {:?}",
+                    receipt.transaction_hash
+                )))
+        }))
+        .await;
+        
+        let mut created = Vec::new();
+        let mut failed = Vec::new();
+        for (index, (spec, outcome)) in streams.into_iter().zip(outcomes).enumerate() {
+            match outcome {
+                Ok((tx_hash, stream_id)) => created.push(StreamResult {
+                    tx_hash,
+                    stream_id,
+                    total_amount: spec.total_amount,
+                    start_time: spec.start_time,
+                    end_time: spec.end_time,
+                }),
+                Err(e) => failed.push((index, e.to_string())),
+            }
+        }
+        
+        if failed.is_empty() {
+            Ok(created)
+        } else {
+            Err(funded(funding_tx)(SynapseError::PartialStreamFailure { created, failed }))
+        }
+    }
+    
+    /// Split `total_amount` across `recipients` by `weights` (see
+    /// `split_by_weights`) and stream each share over the same period
+    pub async fn split_stream(
+        &self,
+        recipients: &[Address],
+        total_amount: impl Into<U256>,
+        weights: &[u32],
+        start_time: U256,
+        end_time: U256,
+    ) -> Result<Vec<StreamResult>> {
+        let total_amount = total_amount.into();
+        if recipients.len() != weights.len() {
+            return Err(SynapseError::InvalidInput(format!(
+                "{} recipients but {} weights",
+                recipients.len(),
+                weights.len()
+            )));
+        }
+        
+        let shares = split_by_weights(total_amount, weights)?;
+        let streams = recipients.iter()
+            .zip(shares)
+            .map(|(recipient, share)| StreamSpec {
+                recipient: *recipient,
+                total_amount: share,
+                start_time,
+                end_time,
+            })
+            .collect();
+        
+        self.create_streams(streams).await
+    }
+    
+    /// Get stream information
+    pub async fn get_stream(&self, stream_id: [u8; 32]) -> Result<StreamInfo> {
+        let stream = self.call_with_retry(self.router.streams(stream_id)).await?;
+        
+        if stream.0 == Address::zero() {
+            return Err(SynapseError::StreamNotFound);
+        }
+        
+        Ok(StreamInfo {
+            sender: stream.0,
+            recipient: stream.1,
+            total_amount: stream.2,
+            withdrawn: stream.3,
+            start_time: stream.4,
+            end_time: stream.5,
+            active: stream.6,
+        })
+    }
+    
+    /// Cancel a stream early, returning the unvested amount refunded to the sender
+    pub async fn cancel_stream(&self, stream_id: [u8; 32]) -> Result<U256> {
+        let stream = self.get_stream(stream_id).await?;
+        if stream.sender != self.address() {
+            return Err(SynapseError::NotStreamSender);
+        }
+        
+        let call = self.router.cancel_stream(stream_id);
+        let receipt = self.send_call(call).await?;
+        
+        let refunded = receipt.logs.iter()
+            .filter(|log| log.address == self.config.contracts.payment_router)
+            .find_map(|log| parse_log::<StreamCancelledFilter>(log.clone()).ok())
+            .map(|event| event.refund_amount)
+            .ok_or(SynapseError::TransactionFailed(format!(
+                "No StreamCancelled event in {:?}",
+                receipt.transaction_hash
+            )))?;
+        
+        Ok(refunded)
+    }
+    
+    /// Collect the withdrawable balance of every stream in `stream_ids`.
+    ///
+    /// Streams with nothing to withdraw are skipped; the remaining
+    /// withdrawals are sent back to back with sequential nonces and then
+    /// awaited together. Returns `(stream_id, result)` per withdrawal sent,
+    /// where a successful result is `(tx_hash, gross_amount_withdrawn)`; one
+    /// failed withdrawal does not affect the others.
+    pub async fn withdraw_all_streams(
+        &self,
+        stream_ids: &[[u8; 32]],
+    ) -> Result<Vec<([u8; 32], Result<(H256, U256)>)>> {
+        let balances = futures::future::try_join_all(
+            stream_ids.iter().map(|id| self.call_with_retry(self.router.get_stream_balance(*id))),
+        )
+        .await?;
+        
+        let ids: Vec<[u8; 32]> = stream_ids.iter().copied()
+            .zip(balances)
+            .filter(|(_, balance)| !balance.is_zero())
+            .map(|(id, _)| id)
+            .collect();
+        
+        let calls = ids.iter().map(|id| self.router.withdraw_from_stream(*id)).collect();
+        let outcomes = self.send_calls_pipelined(calls).await;
+        
+        Ok(ids
+            .into_iter()
+            .zip(outcomes)
+            .map(|(id, outcome)| {
+                let withdrawal = outcome.and_then(|receipt| {
+                    receipt.logs.iter()
+                        .filter(|log| log.address == self.config.contracts.payment_router)
+                        .find_map(|log| parse_log::<StreamWithdrawalFilter>(log.clone()).ok())
+                        .map(|event| (receipt.transaction_hash, event.amount))
+                        .ok_or(SynapseError::TransactionFailed(format!(
+                            "No StreamWithdrawal event in {:?}",
+                            receipt.transaction_hash
+                        )))
+                });
+                (id, withdrawal)
+            })
+            .collect())
+    }
+    
+    /// Vested-but-unwithdrawn amount across `stream_ids` at each of `at_times`.
+    ///
+    /// Uses the streams' current `withdrawn` amounts, so the projection
+    /// assumes nothing is collected in between; inactive streams count as zero.
+    pub async fn cashflow_projection(&self, stream_ids: &[[u8; 32]], at_times: &[U256]) -> Result<Vec<U256>> {
+        let streams = futures::future::try_join_all(stream_ids.iter().map(|id| self.get_stream(*id))).await?;
+        
+        Ok(at_times
+            .iter()
+            .map(|time| {
+                streams.iter()
+                    .filter(|stream| stream.active)
+                    .map(|stream| {
+                        stream_accrued_at(stream.total_amount, stream.start_time, stream.end_time, *time)
+                            .saturating_sub(stream.withdrawn)
+                    })
+                    .fold(U256::zero(), |total, available| total.saturating_add(available))
+            })
+            .collect())
+    }
+    
+    /// Refund owed for a subscription cancelled mid-period; see `prorated_refund`
+    pub fn prorated_refund(&self, paid_amount: U256, period_start: U256, period_end: U256, cancel_time: U256) -> U256 {
+        prorated_refund(paid_amount, period_start, period_end, cancel_time)
+    }
+    
+    /// Timestamp at which `stream_id` will have accrued `target` in total
+    /// (withdrawn amounts included), or `None` if the stream is inactive or
+    /// ends before reaching it
+    pub async fn stream_eta(&self, stream_id: [u8; 32], target: U256) -> Result<Option<U256>> {
+        let stream = self.get_stream(stream_id).await?;
+        if !stream.active {
+            return Ok(None);
+        }
+        
+        Ok(stream_vesting_time(stream.total_amount, stream.start_time, stream.end_time, target))
+    }
+    
+    /// Sign an authorization letting `delegate` withdraw up to `max_amount`
+    /// from one of this wallet's incoming streams.
+    ///
+    /// The signature covers `stream_withdrawal_authorization_hash`. A router
+    /// honouring it must: recompute the hash with its own chain id and
+    /// address, recover the signer and require it to be the stream's
+    /// recipient, require `msg.sender == delegate`, track the cumulative
+    /// amount withdrawn per `(streamId, delegate)` and reject withdrawals past
+    /// `max_amount`, and still pay the funds to the recipient.
+    pub async fn authorize_stream_withdrawal(
+        &self,
+        stream_id: [u8; 32],
+        delegate: Address,
+        max_amount: impl Into<U256>,
+    ) -> Result<Bytes> {
+        let max_amount = max_amount.into();
+        let stream = self.get_stream(stream_id).await?;
+        if stream.recipient != self.address() {
+            return Err(SynapseError::InvalidInput("Caller is not the stream recipient".to_string()));
+        }
+        
+        let hash = stream_withdrawal_authorization_hash(
+            self.config.chain_id,
+            self.config.contracts.payment_router,
+            stream_id,
+            stream.recipient,
+            delegate,
+            max_amount,
+        );
+        let signature = self.wallet.sign_hash(H256::from(hash))?;
+        
+        Ok(signature.to_vec().into())
+    }
+    
+    /// Execute several protocol operations.
+    ///
+    /// With a batcher configured (see `with_batcher`) all calls are packed
+    /// into one atomic transaction and a single hash is returned; otherwise
+    /// they are sent one after another and a hash per call is returned.
+    pub async fn execute_batch(&self, calls: Vec<SynapseCall>) -> Result<Vec<H256>> {
+        let calls: Vec<ContractCall<SignerClient, bool>> = calls
+            .into_iter()
+            .map(|call| self.contract_call(call))
+            .collect();
+        
+        match self.config.batcher {
+            Some(batcher) => {
+                let mut targets = Vec::with_capacity(calls.len());
+                let mut data = Vec::with_capacity(calls.len());
+                for call in &calls {
+                    let target = call.tx.to_addr().copied()
+                        .ok_or(SynapseError::ConfigError("Call without target".to_string()))?;
+                    targets.push(target);
+                    data.push(call.calldata().unwrap_or_default());
+                }
+                
+                let batcher = SynapseBatcher::new(batcher, self.provider.clone());
+                let receipt = self.send_call(batcher.execute(targets, data)).await?;
+                Ok(vec![receipt.transaction_hash])
+            }
+            None => {
+                let mut hashes = Vec::with_capacity(calls.len());
+                for call in calls {
+                    let receipt = self.send_call(call).await?;
+                    hashes.push(receipt.transaction_hash);
+                }
+                Ok(hashes)
+            }
+        }
+    }
+    
+    /// Build the contract call for a bundled operation
+    fn contract_call(&self, call: SynapseCall) -> ContractCall<SignerClient, bool> {
+        match call {
+            SynapseCall::Pay { recipient, amount, metadata } => {
+                let payment_id = self.generate_payment_id("pay");
+                self.router.pay(recipient, amount, payment_id, metadata)
+            }
+            SynapseCall::CreateEscrow { escrow_id, recipient, arbiter, amount, deadline } => {
+                self.router.create_escrow(recipient, arbiter, amount, deadline, escrow_id, Bytes::default())
+            }
+            SynapseCall::ReleaseEscrow { escrow_id } => self.router.release_escrow(escrow_id),
+            SynapseCall::IncreaseStake { amount } => self.reputation.increase_stake(amount),
+            SynapseCall::UpdateService { service_id, description, endpoint, base_price } => {
+                self.services.update_service(service_id, description, endpoint, base_price)
+            }
+        }
+    }
+    
+    // ==================== Agent Functions ====================
+    
+    /// Register as an AI agent
+    pub async fn register_agent(
+        &self,
+        name: &str,
+        metadata_uri: &str,
+        stake: impl Into<U256>,
+    ) -> Result<H256> {
+        let call = self.reputation
+            .register_agent(name.to_string(), metadata_uri.to_string(), stake.into());
+        let receipt = self.send_call(call).await?;
+        
+        Ok(receipt.transaction_hash)
+    }
+    
+    /// Deregister this agent and withdraw its stake
+    pub async fn deregister_agent(&self) -> Result<H256> {
+        let call = self.reputation.deregister_agent();
+        let receipt = self.send_call(call).await?;
+        
+        Ok(receipt.transaction_hash)
+    }
+    
+    /// Whether no agent is registered under `name`.
+    ///
+    /// Needs a registry that enforces unique names and exposes `nameToAgent`;
+    /// against one without it the call reverts and the error is returned.
+    pub async fn is_name_available(&self, name: &str) -> Result<bool> {
+        let owner = self.call_with_retry(self.reputation.name_to_agent(name.to_string())).await?;
+        Ok(owner == Address::zero())
+    }
+    
+    /// Time left before stake released by `decrease_stake` becomes
+    /// spendable, or `None` if no cooldown is running.
+    ///
+    /// The cooldown end is compared to the latest block timestamp.
+    pub async fn stake_cooldown_remaining(&self) -> Result<Option<Duration>> {
+        let (cooldown_end, now) = futures::try_join!(
+            self.call_with_retry(self.reputation.withdrawal_cooldown_end(self.address())),
+            self.chain_time(),
+        )?;
+        
+        if cooldown_end <= now {
+            return Ok(None);
+        }
+        Ok(Some(Duration::from_secs((cooldown_end - now).low_u64())))
+    }
+    
+    /// Authorize `new_signer` as this agent's operator key, keeping the
+    /// registration and reputation bound to the current (identity) address.
+    ///
+    /// Sequencing: `setOperator` is sent from the identity key and waited
+    /// on for the configured confirmations, then `operatorOf` is read back.
+    /// Only after this returns should the runtime switch to the new key.
+    /// The previous operator is replaced in the same transaction, so there
+    /// is no window where neither key is authorized; the identity key
+    /// itself is never invalidated and can rotate again.
+    pub async fn rotate_key(&self, new_signer: Address) -> Result<Vec<H256>> {
+        if new_signer == Address::zero() || new_signer == self.address() {
+            return Err(SynapseError::InvalidInput(format!("Cannot rotate to {:?}", new_signer)));
+        }
+        
+        let agent = self.get_agent(self.address()).await?;
+        if !agent.registered {
+            return Err(SynapseError::AgentNotRegistered);
+        }
+        
+        let receipt = self.send_call(self.reputation.set_operator(new_signer)).await?;
+        let operator = self.call_with_retry(self.reputation.operator_of(self.address())).await?;
+        if operator != new_signer {
+            return Err(SynapseError::TransactionFailed(format!(
+                "Operator is {:?} after rotation to {:?}",
+                operator, new_signer
+            )));
+        }
+        
+        Ok(vec![receipt.transaction_hash])
+    }
+    
+    /// Estimate the total cost of registering with `stake`.
+    ///
+    /// Gas is estimated against the registry; if the estimate reverts (e.g.
+    /// the stake is not approved yet) `REGISTER_AGENT_GAS` is used instead.
+    pub async fn registration_cost(&self, stake: impl Into<U256>) -> Result<RegistrationCost> {
+        let stake = stake.into();
+        let min_stake = self.call_with_retry(self.reputation.min_stake()).await?;
+        let registration_fee = self.call_with_retry(self.reputation.registration_fee()).await?;
+        
+        let gas_estimate = self.reputation
+            .register_agent(String::new(), String::new(), stake)
+            .estimate_gas()
+            .await
+            .unwrap_or_else(|_| U256::from(REGISTER_AGENT_GAS));
+        let gas_price = self.provider.provider().get_gas_price().await?;
+        
+        Ok(RegistrationCost {
+            stake,
+            min_stake,
+            registration_fee,
+            total_synx: stake.saturating_add(registration_fee),
+            gas_estimate,
+            gas_price,
+            gas_cost: gas_estimate.saturating_mul(gas_price),
+        })
+    }
+    
+    /// Get agent information
+    ///
+    /// Only the `agents()` read is required; if `getTier` or `getSuccessRate`
+    /// revert (e.g. for unregistered addresses) they default to
+    /// `Tier::Unverified` and `0.0`. Any other failure is returned.
+    pub async fn get_agent(&self, address: Address) -> Result<AgentInfo> {
+        let agent = self.call_with_retry(self.reputation.agents(address)).await?;
+        
+        let tier = match self.call_with_retry(self.reputation.get_tier(address)).await {
+            Err(SynapseError::CallReverted(e)) => {
+                log::debug!("getTier reverted for {:?}, defaulting to Unverified: {}", address, e);
+                Tier::Unverified as u8
+            }
+            result => result?,
+        };
+        
+        let success_rate = match self.call_with_retry(self.reputation.get_success_rate(address)).await {
+            Err(SynapseError::CallReverted(e)) => {
+                log::debug!("getSuccessRate reverted for {:?}, defaulting to 0: {}", address, e);
+                U256::zero()
+            }
+            result => result?,
+        };
+        
+        Ok(AgentInfo {
+            registered: agent.0,
+            name: agent.1,
+            stake: agent.2,
+            reputation_score: agent.3,
+            total_transactions: agent.4,
+            successful_transactions: agent.5,
+            registered_at: agent.6,
+            metadata_uri: agent.7,
+            tier: Tier::from(tier),
+            success_rate: success_rate.as_u64() as f64 / 100.0,
+        })
+    }
+    
+    /// Agree on protocol parameters with a counterparty advertising
+    /// `counterparty_caps`, starting from everything this SDK supports
+    pub fn negotiate(&self, counterparty_caps: &Capabilities) -> NegotiatedSession {
+        Capabilities::sdk().intersect(counterparty_caps)
+    }
+    
+    /// Sign a claim binding this wallet to its registered agent name and `nonce`
+    pub async fn sign_identity_claim(&self, nonce: [u8; 32]) -> Result<IdentityClaim> {
+        let agent = self.get_agent(self.address()).await?;
+        if !agent.registered {
+            return Err(SynapseError::AgentNotRegistered);
+        }
+        
+        let mut claim = IdentityClaim {
+            agent: self.address(),
+            name: agent.name,
+            nonce,
+            chain_id: self.config.chain_id,
+            registry: self.config.contracts.reputation,
+            signature: Bytes::default(),
+        };
+        let signature = self.wallet.sign_hash(H256::from(claim.hash()))?;
+        claim.signature = signature.to_vec().into();
+        
+        Ok(claim)
+    }
+    
+    /// Check a claim against this client's network and the on-chain registration.
+    ///
+    /// Valid only if the signature recovers to `agent`, the claim targets this
+    /// chain and registry, and `agent` is currently registered under `name`.
+    /// The caller is responsible for checking that `nonce` is the one it issued.
+    pub async fn verify_identity_claim(&self, claim: &IdentityClaim) -> Result<bool> {
+        if claim.chain_id != self.config.chain_id
+            || claim.registry != self.config.contracts.reputation
+            || !claim.verify_signature()
+        {
+            return Ok(false);
+        }
+        
+        let agent = self.get_agent(claim.agent).await?;
+        Ok(agent.registered && agent.name == claim.name)
+    }
+    
+    /// Reputation score changes of `agent` in `[from_block, to_block]` as
+    /// `(block, new_score)` pairs, oldest first
+    pub async fn reputation_history(
+        &self,
+        agent: Address,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<Vec<(u64, U256)>> {
+        let filter = self.reputation.event::<ReputationUpdatedFilter>()
+            .topic1(H256::from(agent))
+            .filter;
+        let events = self.scan_logs::<ReputationUpdatedFilter>(filter, from_block, to_block).await?;
+        
+        Ok(events
+            .into_iter()
+            .map(|(event, meta)| (meta.block_number.as_u64(), event.new_score))
+            .collect())
+    }
+    
+    /// Fetch and decode the off-chain metadata document of an agent.
+    ///
+    /// If the registered URI carries a document hash (see
+    /// `metadata_uri_with_hash`), a document that does not match it is
+    /// rejected with `MetadataError`.
+    #[cfg(feature = "metadata")]
+    pub async fn fetch_agent_metadata(&self, agent: Address) -> Result<AgentMetadata> {
+        let info = self.get_agent(agent).await?;
+        if !info.registered {
+            return Err(SynapseError::AgentNotRegistered);
+        }
+        
+        let url = resolve_metadata_uri(&info.metadata_uri, &self.config.ipfs_gateway)?;
+        
+        let response = reqwest::get(&url).await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| SynapseError::MetadataError(e.to_string()))?;
+        
+        let doc = response.json::<AgentMetadata>().await
+            .map_err(|e| SynapseError::MetadataError(e.to_string()))?;
+        
+        if let Some(expected) = metadata_uri_hash(&info.metadata_uri) {
+            if metadata_document_hash(&doc) != expected {
+                return Err(SynapseError::MetadataError(format!(
+                    "Document at {} does not match its registered hash",
+                    url
+                )));
+            }
+        }
+        
+        Ok(doc)
+    }
+    
+    /// Read candidates' reputation concurrently and sort them by `agent_score`,
+    /// best first. Each entry is `(agent, tier, success_rate, stake)`.
+    pub async fn rank_candidates(&self, candidates: &[Address]) -> Result<Vec<(Address, Tier, f64, U256)>> {
+        let agents = futures::future::try_join_all(
+            candidates.iter().map(|candidate| self.get_agent(*candidate)),
+        )
+        .await?;
+        
+        let mut scored: Vec<(f64, (Address, Tier, f64, U256))> = candidates.iter()
+            .zip(agents)
+            .map(|(address, agent)| (agent_score(&agent), (*address, agent.tier, agent.success_rate, agent.stake)))
+            .collect();
+        scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+        
+        Ok(scored.into_iter().map(|(_, candidate)| candidate).collect())
+    }
+    
+    /// Aggregate trust of a pool of agents; see `pool_score`
+    pub async fn pool_reputation(&self, agents: &[Address]) -> Result<f64> {
+        let infos = futures::future::try_join_all(agents.iter().map(|agent| self.get_agent(*agent))).await?;
+        Ok(pool_score(&infos))
+    }
+    
+    /// Risk in `[0, 1]` that a provider is about to leave the marketplace,
+    /// from its reputation updates and service requests over the last
+    /// `window_blocks`; see `churn_risk_score`
+    pub async fn churn_risk(&self, agent: Address, window_blocks: u64) -> Result<f64> {
+        let head = self.provider.provider().get_block_number().await?.as_u64();
+        let from = head.saturating_sub(window_blocks);
+        let midpoint = from + (head - from) / 2;
+        
+        let reputation_filter = self.reputation.event::<ReputationUpdatedFilter>()
+            .topic1(H256::from(agent))
+            .filter;
+        let (agent_info, updates, service_ids) = futures::try_join!(
+            self.get_agent(agent),
+            self.scan_logs::<ReputationUpdatedFilter>(reputation_filter, from, head),
+            self.services_by_provider(agent),
+        )?;
+        
+        let requests = futures::future::try_join_all(service_ids.iter().map(|id| {
+            let filter = self.services.event::<ServiceRequestFilter>().topic1(H256::from(*id)).filter;
+            self.scan_logs::<ServiceRequestFilter>(filter, from, head)
+        }))
+        .await?;
+        let (first_half, second_half) = requests.iter()
+            .flatten()
+            .fold((0u64, 0u64), |(first, second), (_, meta)| {
+                if meta.block_number.as_u64() <= midpoint { (first + 1, second) } else { (first, second + 1) }
+            });
+        
+        let score_end = agent_info.reputation_score;
+        let score_start = updates.first().map(|(event, _)| event.old_score).unwrap_or(score_end);
+        
+        Ok(churn_risk_score(score_start, score_end, first_half, second_half))
+    }
+    
+    /// Read the registry's requirements for every tier
+    pub async fn tier_requirements(&self) -> Result<Vec<TierRequirement>> {
+        let tiers = [Tier::Unverified, Tier::Bronze, Tier::Silver, Tier::Gold, Tier::Platinum, Tier::Diamond];
+        let raw = futures::future::try_join_all(
+            tiers.iter().map(|tier| self.call_with_retry(self.reputation.tier_requirements(*tier as u8))),
+        )
+        .await?;
+        
+        Ok(tiers.into_iter()
+            .zip(raw)
+            .map(|(tier, req)| TierRequirement {
+                tier,
+                min_transactions: req.0,
+                min_success_rate_bps: req.1,
+                min_stake: req.2,
+                fee_discount_bps: req.3,
+            })
+            .collect())
+    }
+    
+    /// Profit-maximizing stake for this agent given its expected monthly
+    /// revenue and annual cost of capital; see `optimal_stake_for`
+    pub async fn optimal_stake(&self, expected_monthly_revenue: U256, capital_cost_bps: u16) -> Result<U256> {
+        let (agent, requirements, min_stake) = futures::try_join!(
+            self.get_agent(self.address()),
+            self.tier_requirements(),
+            self.call_with_retry(self.reputation.min_stake()),
+        )?;
+        
+        Ok(optimal_stake_for(
+            agent.total_transactions,
+            agent.successful_transactions,
+            &requirements,
+            min_stake,
+            expected_monthly_revenue,
+            capital_cost_bps,
+        ))
+    }
+    
+    /// Annualized return on the stake required for this agent's current
+    /// tier, from payments received over the last `window_blocks`; see
+    /// `stake_yield_for`
+    pub async fn stake_yield(&self, window_blocks: u64) -> Result<f64> {
+        let provider = self.provider.provider();
+        let head = provider.get_block_number().await?.as_u64();
+        let from = head.saturating_sub(window_blocks);
+        
+        let filter = self.router.event::<PaymentFilter>().topic2(H256::from(self.address())).filter;
+        let (agent, requirements, payments, oldest, newest) = futures::try_join!(
+            self.get_agent(self.address()),
+            self.tier_requirements(),
+            self.payment_events(filter, from, head),
+            async { provider.get_block(from).await.map_err(SynapseError::from) },
+            async { provider.get_block(head).await.map_err(SynapseError::from) },
+        )?;
+        
+        let (oldest, newest) = oldest.zip(newest)
+            .ok_or(SynapseError::ContractError("Window boundary block not found".to_string()))?;
+        let elapsed = newest.timestamp.saturating_sub(oldest.timestamp).low_u64();
+        let revenue = payments.iter()
+            .fold(U256::zero(), |total, p| total.saturating_add(p.amount.saturating_sub(p.fee)));
+        let tier_min_stake = requirements.iter()
+            .find(|req| req.tier == agent.tier)
+            .map(|req| req.min_stake)
+            .unwrap_or_default();
+        
+        Ok(stake_yield_for(revenue, elapsed, agent.tier, tier_min_stake))
+    }
+    
+    /// Expected value of accepting a service request; see
+    /// `request_expected_value`. Get `reputation_impact` from `failure_impact`.
+    pub fn request_ev(
+        &self,
+        offered_amount: U256,
+        estimated_cost: U256,
+        failure_probability: f64,
+        reputation_impact: &FailureImpact,
+    ) -> f64 {
+        request_expected_value(offered_amount, estimated_cost, failure_probability, reputation_impact)
+    }
+    
+    /// Project this agent's success rate, score and tier after one more
+    /// failed transaction
+    pub async fn failure_impact(&self) -> Result<FailureImpact> {
+        let (agent, requirements) = futures::try_join!(
+            self.get_agent(self.address()),
+            self.tier_requirements(),
+        )?;
+        
+        let total = agent.total_transactions + 1;
+        let projected_success_rate = u256_to_f64(agent.successful_transactions) * 100.0 / u256_to_f64(total);
+        
+        Ok(FailureImpact {
+            current_tier: agent.tier,
+            projected_tier: project_tier(total, agent.successful_transactions, agent.stake, &requirements),
+            current_success_rate: agent.success_rate,
+            projected_success_rate,
+            current_score: agent.reputation_score,
+            projected_score: agent.reputation_score.saturating_sub(U256::from(FAILURE_SCORE_PENALTY)),
+        })
+    }
+    
+    /// Increase stake, funded from the payer if one is configured
+    pub async fn increase_stake(&self, amount: impl Into<U256>) -> Result<H256> {
+        let amount = amount.into();
+        let funding_tx = self.fund_from_payer(amount).await?;
+        
+        let call = self.reputation.increase_stake(amount);
+        let receipt = self.send_call(call).await.map_err(funded(funding_tx))?;
+        
+        Ok(receipt.transaction_hash)
+    }
+    
+    /// Increase stake, first approving the reputation registry for `amount`
+    /// if its current allowance does not cover it
+    pub async fn increase_stake_checked(&self, amount: impl Into<U256>) -> Result<H256> {
+        let amount = amount.into();
+        self.approve_if_needed(self.config.contracts.reputation, amount).await?;
+        self.increase_stake(amount).await
+    }
+    
+    // ==================== Service Functions ====================
+    
+    /// Register a service
+    pub async fn register_service(
+        &self,
+        name: &str,
+        category: &str,
+        description: &str,
+        endpoint: &str,
+        base_price: impl Into<U256>,
+        pricing_model: PricingModel,
+    ) -> Result<H256> {
+        let base_price = base_price.into();
+        let call = self.services
+            .register_service(
+                name.to_string(),
+                category.to_string(),
+                description.to_string(),
+                endpoint.to_string(),
+                base_price,
+                pricing_model as u8,
+            );
+        let receipt = self.send_call(call).await?;
+        
+        Ok(receipt.transaction_hash)
+    }
+    
+    /// Whether this wallet is the provider of a service
+    pub async fn owns_service(&self, service_id: [u8; 32]) -> Result<bool> {
+        let service = self.get_service(service_id).await?;
+        Ok(service.provider == self.address())
+    }
+    
+    /// Fail with `NotServiceOwner` unless this wallet provides the service
+    async fn ensure_service_owner(&self, service_id: [u8; 32]) -> Result<()> {
+        if self.owns_service(service_id).await? {
+            Ok(())
+        } else {
+            Err(SynapseError::NotServiceOwner(hex::encode(service_id)))
+        }
+    }
+    
+    /// Update a service's description, endpoint and base price
+    pub async fn update_service(
+        &self,
+        service_id: [u8; 32],
+        description: &str,
+        endpoint: &str,
+        base_price: impl Into<U256>,
+    ) -> Result<H256> {
+        let base_price = base_price.into();
+        self.ensure_service_owner(service_id).await?;
+        
+        let call = self.services
+            .update_service(service_id, description.to_string(), endpoint.to_string(), base_price);
+        let receipt = self.send_call(call).await?;
+        
+        Ok(receipt.transaction_hash)
+    }
+    
+    /// Change the base price of several services, keeping their description
+    /// and endpoint.
+    ///
+    /// Every service is checked to belong to this wallet before anything is
+    /// sent; the updates then go out back to back with sequential nonces.
+    /// Returns `(service_id, result)` per update, in order; one failed update
+    /// does not affect the others.
+    pub async fn update_prices(&self, updates: Vec<([u8; 32], U256)>) -> Result<Vec<([u8; 32], Result<H256>)>> {
+        let services = futures::future::try_join_all(
+            updates.iter().map(|(service_id, _)| self.get_service(*service_id)),
+        )
+        .await?;
+        
+        if let Some(((service_id, _), _)) = updates.iter()
+            .zip(&services)
+            .find(|(_, service)| service.provider != self.address())
+        {
+            return Err(SynapseError::NotServiceOwner(hex::encode(service_id)));
+        }
+        
+        let calls = updates.iter()
+            .zip(services)
+            .map(|((service_id, price), service)| {
+                self.services.update_service(*service_id, service.description, service.endpoint, *price)
+            })
+            .collect();
+        let outcomes = self.send_calls_pipelined(calls).await;
+        
+        Ok(updates
+            .into_iter()
+            .zip(outcomes)
+            .map(|((service_id, _), outcome)| (service_id, outcome.map(|receipt| receipt.transaction_hash)))
+            .collect())
+    }
+    
+    /// Deactivate every active service of this wallet, pipelined, and
+    /// remember the ones whose deactivation confirmed so
+    /// `resume_all_services` brings back only those.
+    ///
+    /// The remembered set lives in this client only; persist
+    /// `PauseReport::paused` and hand it back with `restore_paused_services`
+    /// to resume from another process.
+    pub async fn pause_all_services(&self) -> Result<PauseReport> {
+        let ids = self.services_by_provider(self.address()).await?;
+        let infos = futures::future::try_join_all(ids.iter().map(|id| self.get_service(*id))).await?;
+        let active: Vec<[u8; 32]> = ids.into_iter()
+            .zip(infos)
+            .filter(|(_, info)| info.active)
+            .map(|(id, _)| id)
+            .collect();
+        
+        let calls = active.iter().map(|id| self.services.deactivate_service(*id)).collect();
+        let outcomes: Vec<([u8; 32], Result<H256>)> = active.into_iter()
+            .zip(self.send_calls_pipelined(calls).await)
+            .map(|(id, outcome)| (id, outcome.map(|receipt| receipt.transaction_hash)))
+            .collect();
+        
+        let mut paused = self.paused_services.lock().unwrap_or_else(|e| e.into_inner());
+        for (id, _) in outcomes.iter().filter(|(_, outcome)| outcome.is_ok()) {
+            if !paused.contains(id) {
+                paused.push(*id);
+            }
+        }
+        
+        Ok(PauseReport { outcomes, paused: paused.clone() })
+    }
+    
+    /// Services remembered as paused by `pause_all_services`
+    pub fn paused_services(&self) -> Vec<[u8; 32]> {
+        self.paused_services.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+    
+    /// Replace the remembered paused set, e.g. with a persisted `PauseReport::paused`
+    pub fn restore_paused_services(&self, service_ids: Vec<[u8; 32]>) {
+        *self.paused_services.lock().unwrap_or_else(|e| e.into_inner()) = service_ids;
+    }
+    
+    /// Reactivate the services paused by `pause_all_services` that are still
+    /// inactive, pipelined. Services that were already off stay off.
+    ///
+    /// Returns `(service_id, result)` per activation sent. Services whose
+    /// activation failed stay remembered as paused so a later call retries them.
+    pub async fn resume_all_services(&self) -> Result<Vec<([u8; 32], Result<H256>)>> {
+        let paused = self.paused_services();
+        let infos = futures::future::try_join_all(paused.iter().map(|id| self.get_service(*id))).await?;
+        let inactive: Vec<[u8; 32]> = paused.into_iter()
+            .zip(infos)
+            .filter(|(_, info)| !info.active)
+            .map(|(id, _)| id)
+            .collect();
+        
+        let calls = inactive.iter().map(|id| self.services.activate_service(*id)).collect();
+        let outcomes: Vec<([u8; 32], Result<H256>)> = inactive.into_iter()
+            .zip(self.send_calls_pipelined(calls).await)
+            .map(|(id, outcome)| (id, outcome.map(|receipt| receipt.transaction_hash)))
+            .collect();
+        
+        let failed: Vec<[u8; 32]> = outcomes.iter()
+            .filter(|(_, outcome)| outcome.is_err())
+            .map(|(id, _)| *id)
+            .collect();
+        self.paused_services.lock().unwrap_or_else(|e| e.into_inner()).retain(|id| failed.contains(id));
+        
+        Ok(outcomes)
+    }
+    
+    /// Deactivate a service
+    pub async fn deactivate_service(&self, service_id: [u8; 32]) -> Result<H256> {
+        self.ensure_service_owner(service_id).await?;
+        
+        let call = self.services.deactivate_service(service_id);
+        let receipt = self.send_call(call).await?;
+        
+        Ok(receipt.transaction_hash)
+    }
+    
+    /// Reactivate a service
+    pub async fn activate_service(&self, service_id: [u8; 32]) -> Result<H256> {
+        self.ensure_service_owner(service_id).await?;
+        
+        let call = self.services.activate_service(service_id);
+        let receipt = self.send_call(call).await?;
+        
+        Ok(receipt.transaction_hash)
+    }
+    
+    /// Get service information
+    pub async fn get_service(&self, service_id: [u8; 32]) -> Result<ServiceInfo> {
+        self.get_service_at(service_id, None).await
+    }
+    
+    /// Service information as of `block` (latest if `None`); historical
+    /// blocks need an archive node
+    pub async fn get_service_at(&self, service_id: [u8; 32], block: Option<u64>) -> Result<ServiceInfo> {
+        let mut call = self.services.services(service_id);
+        if let Some(block) = block {
+            call = call.block(block);
+        }
+        let service = self.call_with_retry(call).await?;
+        
+        Ok(ServiceInfo {
+            provider: service.0,
+            name: service.1,
+            category: service.2,
+            description: service.3,
+            endpoint: service.4,
+            base_price: service.5,
+            pricing_model: PricingModel::from(service.6),
+            active: service.7,
+            total_requests: service.8,
+            total_revenue: service.9,
+            created_at: service.10,
+        })
+    }
+    
+    /// Find services by category
+    pub async fn find_services(&self, category: &str) -> Result<Vec<[u8; 32]>> {
+        let services = self
+            .call_with_retry(self.services.get_services_by_category(category.to_string()))
+            .await?;
+        
+        Ok(services)
+    }
+    
+    /// Services registered by `provider`
+    pub async fn services_by_provider(&self, provider: Address) -> Result<Vec<[u8; 32]>> {
+        let services = self
+            .call_with_retry(self.services.get_services_by_provider(provider))
+            .await?;
+        
+        Ok(services)
+    }
+    
+    /// Groups of this wallet's active services sharing a `(name, category)`.
+    ///
+    /// Each group is ordered oldest first, so everything after the first
+    /// entry is a candidate for `deactivate_service`.
+    pub async fn find_duplicate_services(&self) -> Result<Vec<Vec<[u8; 32]>>> {
+        let ids = self.services_by_provider(self.address()).await?;
+        let infos = futures::future::try_join_all(ids.iter().map(|id| self.get_service(*id))).await?;
+        
+        let mut groups: Vec<((String, String), Vec<([u8; 32], U256)>)> = Vec::new();
+        for (id, info) in ids.into_iter().zip(infos).filter(|(_, info)| info.active) {
+            let key = (info.name, info.category);
+            match groups.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, members)) => members.push((id, info.created_at)),
+                None => groups.push((key, vec![(id, info.created_at)])),
+            }
+        }
+        
+        Ok(groups
+            .into_iter()
+            .filter(|(_, members)| members.len() > 1)
+            .map(|(_, mut members)| {
+                members.sort_by_key(|(_, created_at)| *created_at);
+                members.into_iter().map(|(id, _)| id).collect()
+            })
+            .collect())
+    }
+    
+    /// Revenue of a service per hour of wall-clock time over the last `window_blocks`.
+    ///
+    /// Sums the `ServiceRequest` amounts recorded for the service and divides
+    /// by the time between the window's first and last block.
+    pub async fn revenue_rate(&self, service_id: [u8; 32], window_blocks: u64) -> Result<U256> {
+        if window_blocks == 0 {
+            return Err(SynapseError::InvalidInput("Revenue window must span at least one block".to_string()));
+        }
+        
+        let provider = self.provider.provider();
+        let head = provider.get_block_number().await?.as_u64();
+        let from = head.saturating_sub(window_blocks);
+        
+        let filter = self.services.event::<ServiceRequestFilter>()
+            .topic1(H256::from(service_id))
+            .filter;
+        let (requests, oldest, newest) = futures::try_join!(
+            self.scan_logs::<ServiceRequestFilter>(filter, from, head),
+            async { provider.get_block(from).await.map_err(SynapseError::from) },
+            async { provider.get_block(head).await.map_err(SynapseError::from) },
+        )?;
+        
+        let (oldest, newest) = oldest.zip(newest)
+            .ok_or(SynapseError::ContractError("Window boundary block not found".to_string()))?;
+        let elapsed = newest.timestamp.saturating_sub(oldest.timestamp);
+        if elapsed.is_zero() {
+            return Ok(U256::zero());
+        }
+        
+        let revenue = requests.iter()
+            .fold(U256::zero(), |total, (request, _)| total.saturating_add(request.amount));
+        Ok(revenue.saturating_mul(U256::from(3600)) / elapsed)
+    }
+    
+    /// Score the active services of a category, best first
+    async fn ranked_services(&self, category: &str) -> Result<Vec<([u8; 32], ServiceInfo, f64)>> {
+        let ids = self.find_services(category).await?;
+        let infos = futures::future::try_join_all(ids.iter().map(|id| self.get_service(*id))).await?;
+        
+        let active: Vec<([u8; 32], ServiceInfo)> = ids.into_iter()
+            .zip(infos)
+            .filter(|(_, info)| info.active)
+            .collect();
+        let providers = futures::future::try_join_all(
+            active.iter().map(|(_, info)| self.get_agent(info.provider)),
+        )
+        .await?;
+        
+        let mut ranked: Vec<([u8; 32], ServiceInfo, f64)> = active.into_iter()
+            .zip(providers)
+            .map(|((id, info), provider)| {
+                let score = service_score(&provider, info.base_price);
+                (id, info, score)
+            })
+            .collect();
+        
+        ranked.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap_or(std::cmp::Ordering::Equal));
+        Ok(ranked)
+    }
+    
+    /// Find the best active service in a category by `service_score`
+    pub async fn find_best_service(&self, category: &str) -> Result<Option<([u8; 32], ServiceInfo)>> {
+        let ranked = self.ranked_services(category).await?;
+        Ok(ranked.into_iter().next().map(|(id, info, _)| (id, info)))
+    }
+    
+    /// Services added, removed, repriced or toggled in a category between
+    /// `old_block` and `new_block`. Reads historical state, so the RPC
+    /// endpoint must be an archive node.
+    pub async fn diff_category(&self, category: &str, old_block: u64, new_block: u64) -> Result<CategoryDiff> {
+        let (old, new) = futures::try_join!(
+            self.category_services_at(category, old_block),
+            self.category_services_at(category, new_block),
+        )?;
+        
+        let mut diff = CategoryDiff::default();
+        for (id, info) in &new {
+            match old.iter().find(|(old_id, _)| old_id == id) {
+                None => diff.added.push(*id),
+                Some((_, before)) if before.base_price != info.base_price || before.active != info.active => {
+                    diff.modified.push(ServiceChange {
+                        service_id: *id,
+                        old_price: before.base_price,
+                        new_price: info.base_price,
+                        was_active: before.active,
+                        is_active: info.active,
+                    });
+                }
+                Some(_) => {}
+            }
+        }
+        diff.removed = old.iter()
+            .filter(|(id, _)| !new.iter().any(|(new_id, _)| new_id == id))
+            .map(|(id, _)| *id)
+            .collect();
+        
+        Ok(diff)
+    }
+    
+    /// Services listed under a category as of `block`
+    async fn category_services_at(&self, category: &str, block: u64) -> Result<Vec<([u8; 32], ServiceInfo)>> {
+        let ids = self
+            .call_with_retry(self.services.get_services_by_category(category.to_string()).block(block))
+            .await?;
+        let infos = futures::future::try_join_all(ids.iter().map(|id| self.get_service_at(*id, Some(block)))).await?;
+        
+        Ok(ids.into_iter().zip(infos).collect())
+    }
+    
+    /// Active service in a category that is cheapest to buy `quantity` of,
+    /// with its full delivered cost.
+    ///
+    /// The SYNX cost is the gross payment that leaves the service price after
+    /// this wallet's protocol fee (see `gross_for_net`); the gas cost of the
+    /// payment (`PAY_GAS` at the current gas price) is reported separately in
+    /// native wei. Gas is the same for every candidate, so only the SYNX cost
+    /// decides which service wins.
+    pub async fn find_cheapest_total(&self, category: &str, quantity: U256) -> Result<Option<CheapestOffer>> {
+        let ids = self.find_services(category).await?;
+        let infos = futures::future::try_join_all(ids.iter().map(|id| self.get_service(*id))).await?;
+        let active: Vec<[u8; 32]> = ids.into_iter()
+            .zip(infos)
+            .filter(|(_, info)| info.active)
+            .map(|(id, _)| id)
+            .collect();
+        if active.is_empty() {
+            return Ok(None);
+        }
+        
+        let (prices, fee_bps, discount_bps, gas_price) = futures::try_join!(
+            futures::future::try_join_all(active.iter().map(|id| self.calculate_price(*id, quantity))),
+            self.fee_bps(),
+            self.fee_discount_bps(self.address()),
+            async { self.provider.provider().get_gas_price().await.map_err(SynapseError::from) },
+        )?;
+        let gas_cost = gas_price.saturating_mul(U256::from(PAY_GAS));
+        
+        Ok(active
+            .into_iter()
+            .zip(prices)
+            .filter_map(|(service_id, price)| {
+                let synx_total = gross_for_net(price, fee_bps, discount_bps)?;
+                Some(CheapestOffer { service_id, synx_total, gas_wei: gas_cost })
+            })
+            .min_by_key(|offer| offer.synx_total))
+    }
+    
+    /// Fraction (0.0-1.0) of its category's total requests served by a service;
+    /// 0.0 if the category has no requests yet
+    pub async fn market_share(&self, service_id: [u8; 32]) -> Result<f64> {
+        let service = self.get_service(service_id).await?;
+        let ids = self.find_services(&service.category).await?;
+        let peers = futures::future::try_join_all(ids.iter().map(|id| self.get_service(*id))).await?;
+        
+        let category_requests = peers.iter()
+            .fold(U256::zero(), |total, peer| total.saturating_add(peer.total_requests));
+        if category_requests.is_zero() {
+            return Ok(0.0);
+        }
+        
+        Ok(u256_to_f64(service.total_requests) / u256_to_f64(category_requests))
+    }
+    
+    /// This wallet's provider rank within a category as `(rank, total)`.
+    ///
+    /// Providers are ranked by their best-scoring active service; rank is 1-based.
+    pub async fn my_rank(&self, category: &str) -> Result<(usize, usize)> {
+        let ranked = self.ranked_services(category).await?;
+        
+        let mut providers: Vec<Address> = Vec::new();
+        for (_, info, _) in &ranked {
+            if !providers.contains(&info.provider) {
+                providers.push(info.provider);
+            }
+        }
+        
+        let me = self.address();
+        let position = providers.iter().position(|p| *p == me)
+            .ok_or(SynapseError::ServiceNotFound(category.to_string()))?;
+        
+        Ok((position + 1, providers.len()))
+    }
+    
+    /// Watch new service registrations, optionally restricted to one category
+    pub async fn watch_services(
+        &self,
+        category: Option<String>,
+    ) -> Result<impl Stream<Item = ServiceRegisteredFilter> + '_> {
+        let filter = self.services.event::<ServiceRegisteredFilter>().filter;
+        let watcher = self.provider.provider().watch(&filter).await?;
+        
+        Ok(watcher.filter_map(move |log| {
+            let category = category.clone();
+            async move {
+                let event = parse_log::<ServiceRegisteredFilter>(log).ok()?;
+                match category {
+                    Some(c) if c != event.category => None,
+                    _ => Some(event),
+                }
+            }
+        }))
+    }
+    
+    /// Calculate service price
+    pub async fn calculate_price(&self, service_id: [u8; 32], quantity: U256) -> Result<U256> {
+        let price = self.call_with_retry(self.services.calculate_price(service_id, quantity)).await?;
+        
+        Ok(price)
+    }
+    
+    /// Pay a service's provider `calculate_price(quantity)` plus `tip`,
+    /// recording both in `TipMetadata`
+    pub async fn pay_with_tip(
+        &self,
+        service_id: [u8; 32],
+        quantity: U256,
+        tip: impl Into<U256>,
+        metadata: Option<Bytes>,
+    ) -> Result<PaymentResult> {
+        let tip = tip.into();
+        let (service, price) = futures::try_join!(
+            self.get_service(service_id),
+            self.calculate_price(service_id, quantity),
+        )?;
+        if !service.active {
+            return Err(SynapseError::InvalidInput(format!("Service 0x{} is inactive", hex::encode(service_id))));
+        }
+        
+        let tip_metadata = TipMetadata {
+            service_id: service_id.into(),
+            price,
+            tip,
+            data: metadata.unwrap_or_default(),
+        };
+        self.pay(service.provider, price.saturating_add(tip), Some(tip_metadata.encode())).await
+    }
+    
+    /// Estimated time the current price of a service will hold, from its
+    /// `ServiceUpdated` price changes over the last `QUOTE_HISTORY_BLOCKS`;
+    /// see `quote_validity_window`
+    pub async fn quote_validity(&self, service_id: [u8; 32]) -> Result<Duration> {
+        let provider = self.provider.provider();
+        let head = provider.get_block_number().await?.as_u64();
+        let filter = self.services.event::<ServiceUpdatedFilter>()
+            .topic1(H256::from(service_id))
+            .filter;
+        let updates = self.scan_logs::<ServiceUpdatedFilter>(filter, head.saturating_sub(QUOTE_HISTORY_BLOCKS), head).await?;
+        
+        // Status-only updates repeat the price
+        let mut change_blocks: Vec<u64> = Vec::new();
+        let mut last_price = None;
+        for (event, meta) in updates {
+            if last_price != Some(event.new_price) {
+                change_blocks.push(meta.block_number.as_u64());
+                last_price = Some(event.new_price);
+            }
+        }
+        
+        let blocks = futures::future::try_join_all(change_blocks.iter().map(|n| provider.get_block(*n))).await?;
+        let change_times: Vec<u64> = blocks.into_iter()
+            .flatten()
+            .map(|block| block.timestamp.low_u64())
+            .collect();
+        
+        Ok(quote_validity_window(&change_times))
+    }
+    
+    /// Fail with `InvalidInput` unless the service currently uses `expected` pricing
+    pub async fn expect_pricing_model(&self, service_id: [u8; 32], expected: PricingModel) -> Result<()> {
+        let service = self.get_service(service_id).await?;
+        if service.pricing_model != expected {
+            return Err(SynapseError::InvalidInput(format!(
+                "Service 0x{} uses {:?} pricing, expected {:?}",
+                hex::encode(service_id),
+                service.pricing_model,
+                expected
+            )));
+        }
+        
+        Ok(())
+    }
+    
+    /// Largest quantity of a service whose `calculate_price` fits in `budget`.
+    ///
+    /// Linear pricing models are solved directly (and checked against the
+    /// contract); anything else, or a linear model whose on-chain price
+    /// deviates from `base_price * quantity`, is binary-searched.
+    /// Free linear services return `U256::MAX`; the search stops at 2^128.
+    pub async fn max_affordable_quantity(&self, service_id: [u8; 32], budget: U256) -> Result<U256> {
+        let service = self.get_service(service_id).await?;
+        
+        let linear = matches!(
+            service.pricing_model,
+            PricingModel::PerRequest | PricingModel::PerToken | PricingModel::PerSecond | PricingModel::PerByte
+        );
+        if linear {
+            if service.base_price.is_zero() {
+                return Ok(U256::MAX);
+            }
+            let quantity = budget / service.base_price;
+            if quantity.is_zero() || self.calculate_price(service_id, quantity).await? <= budget {
+                return Ok(quantity);
+            }
+        }
+        
+        // Exponential search for an unaffordable upper bound, then bisect
+        let cap = U256::one() << 128;
+        let mut low = U256::zero();
+        let mut high = U256::one();
+        while self.calculate_price(service_id, high).await? <= budget {
+            low = high;
+            if high >= cap {
+                return Ok(low);
+            }
+            high = high << 1;
+        }
+        
+        while high - low > U256::one() {
+            let mid = low + (high - low) / 2;
+            if self.calculate_price(service_id, mid).await? <= budget {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        
+        Ok(low)
+    }
+    
+    // ==================== Channel Functions ====================
+    
+    /// Open a payment channel; `my_deposit` is funded from the payer if one
+    /// is configured
+    pub async fn open_channel(
+        &self,
+        counterparty: Address,
+        my_deposit: impl Into<U256>,
+        their_deposit: impl Into<U256>,
+    ) -> Result<H256> {
+        let my_deposit = my_deposit.into();
+        let funding_tx = self.fund_from_payer(my_deposit).await?;
+        
+        let call = self.channels
+            .open_channel(counterparty, my_deposit, their_deposit.into());
+        let receipt = self.send_call(call).await.map_err(funded(funding_tx))?;
+        
+        Ok(receipt.transaction_hash)
+    }
+    
+    /// Close a channel cooperatively with a state signed by both participants
+    pub async fn cooperative_close(
+        &self,
+        counterparty: Address,
+        balance1: impl Into<U256>,
+        balance2: impl Into<U256>,
+        nonce: U256,
+        sig1: Bytes,
+        sig2: Bytes,
+    ) -> Result<H256> {
+        let (balance1, balance2) = (balance1.into(), balance2.into());
+        let call = self.channels
+            .cooperative_close(counterparty, balance1, balance2, nonce, sig1, sig2);
+        let receipt = self.send_call(call).await?;
+        
+        Ok(receipt.transaction_hash)
+    }
+    
+    /// `(balance1, balance2, nonce, sig1, sig2)` arguments for `cooperative_close`.
+    ///
+    /// `latest` must be signed by both participants stored on-chain, and
+    /// `close_signatures` must hold each participant's signature over its
+    /// `cooperative_close_hash` (see `SignedChannelState::sign_cooperative_close`);
+    /// state signatures are not accepted by the contract for a cooperative close.
+    pub async fn fair_close_state(
+        &self,
+        latest: &SignedChannelState,
+        close_signatures: &[(Address, Bytes)],
+    ) -> Result<(U256, U256, U256, Bytes, Bytes)> {
+        let channel = self.call_with_retry(self.channels.channels(latest.channel_id)).await?;
+        let (participant1, participant2) = (channel.0, channel.1);
+        if participant1 == Address::zero() {
+            return Err(SynapseError::ChannelNotFound);
+        }
+        
+        if !latest.is_signed_by(participant1, participant2) {
+            return Err(SynapseError::InvalidSignature);
+        }
+        let close_signature_of = |participant: Address| {
+            close_signatures.iter()
+                .find(|(signer, sig)| *signer == participant && latest.verify_cooperative_close(participant, sig))
+                .map(|(_, sig)| sig.clone())
+                .ok_or(SynapseError::InvalidSignature)
+        };
+        let sig1 = close_signature_of(participant1)?;
+        let sig2 = close_signature_of(participant2)?;
+        
+        Ok((latest.balance1, latest.balance2, latest.nonce, sig1, sig2))
+    }
+    
+    /// Sign agreement to close a channel cooperatively at the given state
+    pub fn sign_cooperative_close(
+        &self,
+        channel_id: [u8; 32],
+        balance1: impl Into<U256>,
+        balance2: impl Into<U256>,
+        nonce: U256,
+    ) -> Result<Bytes> {
+        let (balance1, balance2) = (balance1.into(), balance2.into());
+        let hash = cooperative_close_hash(channel_id, balance1, balance2, nonce);
+        let signature = self.wallet.sign_hash(channel_state_digest(hash))?;
+        
+        Ok(signature.to_vec().into())
+    }
+    
+    /// Latest of a burst of channel updates after validating the whole
+    /// sequence against the channel's on-chain participants; see
+    /// `coalesce_channel_states`
+    pub async fn coalesce_updates(&self, updates: Vec<SignedChannelState>) -> Result<SignedChannelState> {
+        let channel_id = updates.first()
+            .map(|update| update.channel_id)
+            .ok_or(SynapseError::InvalidInput("No channel updates to coalesce".to_string()))?;
+        let channel = self.call_with_retry(self.channels.channels(channel_id)).await?;
+        if channel.0 == Address::zero() {
+            return Err(SynapseError::ChannelNotFound);
+        }
+        
+        coalesce_channel_states(channel.0, channel.1, updates)
+    }
+    
+    /// Signed record of a channel's settlement, rebuilt from the states
+    /// exchanged over its lifetime; see `SettlementSummary::from_states`
+    pub fn settlement_summary(
+        &self,
+        final_state: &SignedChannelState,
+        events: &[SignedChannelState],
+    ) -> Result<SettlementSummary> {
+        let mut summary = SettlementSummary::from_states(final_state, events)?;
+        summary.sign(&self.wallet)?;
+        
+        Ok(summary)
+    }
+    
+    /// Get channel information
+    pub async fn get_channel(&self, party1: Address, party2: Address) -> Result<ChannelInfo> {
+        let channel_id = self.call_with_retry(self.channels.get_channel_id(party1, party2)).await?;
+        
+        let channel = self.call_with_retry(self.channels.channels(channel_id)).await?;
+        
+        Ok(ChannelInfo {
+            participant1: channel.0,
+            participant2: channel.1,
+            balance1: channel.2,
+            balance2: channel.3,
+            nonce: channel.4,
+            status: ChannelStatus::from(channel.5),
+            challenge_end: channel.6,
+        })
+    }
+    
+    /// Rediscover this wallet's open or closing channels from `ChannelOpened`
+    /// events since `from_block`
+    pub async fn my_open_channels(&self, from_block: u64) -> Result<Vec<ChannelInfo>> {
+        let me = H256::from(self.address());
+        let as_party1 = self.channels.event::<ChannelOpenedFilter>().topic2(me).filter;
+        let as_party2 = self.channels.event::<ChannelOpenedFilter>().topic3(me).filter;
+        let head = self.provider.provider().get_block_number().await?.as_u64();
+        
+        let (opened1, opened2) = futures::try_join!(
+            self.scan_logs::<ChannelOpenedFilter>(as_party1, from_block, head),
+            self.scan_logs::<ChannelOpenedFilter>(as_party2, from_block, head),
+        )?;
+        
+        // A channel reopened between the same parties shares its id
+        let mut pairs: Vec<([u8; 32], Address, Address)> = Vec::new();
+        for (event, _) in opened1.into_iter().chain(opened2) {
+            if !pairs.iter().any(|(id, _, _)| *id == event.channel_id) {
+                pairs.push((event.channel_id, event.party_1, event.party_2));
+            }
+        }
+        
+        let channels = futures::future::try_join_all(
+            pairs.iter().map(|(_, party1, party2)| self.get_channel(*party1, *party2)),
+        )
+        .await?;
+        
+        Ok(channels
+            .into_iter()
+            .filter(|c| matches!(c.status, ChannelStatus::Open | ChannelStatus::Closing))
+            .collect())
+    }
+    
+    /// Sum of this wallet's balance across its open or closing channels with `counterparties`
+    pub async fn total_channel_value(&self, counterparties: &[Address]) -> Result<U256> {
+        let me = self.address();
+        let channels = futures::future::try_join_all(
+            counterparties.iter().map(|counterparty| self.get_channel(me, *counterparty)),
+        )
+        .await?;
+        
+        Ok(channels
+            .iter()
+            .filter(|c| matches!(c.status, ChannelStatus::Open | ChannelStatus::Closing))
+            .map(|c| if c.participant1 == me { c.balance1 } else if c.participant2 == me { c.balance2 } else { U256::zero() })
+            .fold(U256::zero(), |total, balance| total.saturating_add(balance)))
+    }
+    
+    /// Time left to respond to a closing channel, measured against chain time.
+    ///
+    /// Returns `None` unless the channel is `Closing`; an elapsed window is `Duration::ZERO`.
+    pub async fn channel_challenge_remaining(
+        &self,
+        party1: Address,
+        party2: Address,
+    ) -> Result<Option<Duration>> {
+        let channel = self.get_channel(party1, party2).await?;
+        if channel.status != ChannelStatus::Closing {
+            return Ok(None);
+        }
+        
+        let now = self.chain_time().await?;
+        let remaining = channel.challenge_end.saturating_sub(now);
+        Ok(Some(Duration::from_secs(remaining.low_u64())))
+    }
+    
+    /// Open, deposit, close, challenge and dispute events of the channel
+    /// between `party1` and `party2` since `from_block`, oldest first
+    pub async fn channel_events(
+        &self,
+        party1: Address,
+        party2: Address,
+        from_block: u64,
+    ) -> Result<Vec<ChannelEvent>> {
+        let channel_id = self.call_with_retry(self.channels.get_channel_id(party1, party2)).await?;
+        let filter = Filter::new()
+            .address(self.config.contracts.payment_channel)
+            .topic1(H256::from(channel_id));
+        let head = self.provider.provider().get_block_number().await?.as_u64();
+        
+        let mut events: Vec<ChannelEvent> = self
+            .scan_logs::<PaymentChannelEvents>(filter, from_block, head)
+            .await?
+            .into_iter()
+            .map(|(event, meta)| ChannelEvent {
+                channel_id: H256::from(channel_id),
+                kind: event.into(),
+                tx_hash: meta.transaction_hash,
+                block_number: meta.block_number.as_u64(),
+                log_index: meta.log_index.as_u64(),
+            })
+            .collect();
+        events.sort_by_key(|event| event.checkpoint());
+        
+        Ok(events)
+    }
+    
+    /// Risk that a unilateral close with `state` is overturned by a challenge;
+    /// see `CloseRisk::assess`
+    pub fn close_risk(&self, state: &SignedChannelState, counterparty_last_known_nonce: Option<U256>) -> CloseRisk {
+        CloseRisk::assess(state, counterparty_last_known_nonce)
+    }
+    
+    /// Recommend direct payments, a channel or a stream for a relationship
+    /// of `expected_count` payments totalling `expected_volume`.
+    ///
+    /// Compares gas using the `*_GAS` constants. A stream is only considered
+    /// when the payments are spread `over_duration`, and is preferred over a
+    /// channel once it beats direct payments, since it needs no per-payment
+    /// signatures from the counterparty.
+    pub fn recommend_payment_method(
+        &self,
+        counterparty: Address,
+        expected_volume: U256,
+        expected_count: u64,
+        over_duration: Option<Duration>,
+    ) -> PaymentMethodRecommendation {
+        let pay_gas = U256::from(PAY_GAS);
+        let direct_gas = expected_count.saturating_mul(PAY_GAS);
+        let channel_gas = CHANNEL_OPEN_GAS + CHANNEL_CLOSE_GAS;
+        let stream_gas = STREAM_CREATE_GAS + STREAM_WITHDRAW_GAS;
+        
+        let (method, estimated_gas) = if over_duration.is_some()
+            && expected_count > breakeven_payments(U256::from(stream_gas), pay_gas)
+        {
+            (PaymentMethod::Stream, stream_gas)
+        } else if expected_count > breakeven_payments(U256::from(channel_gas), pay_gas) {
+            (PaymentMethod::Channel, channel_gas)
+        } else {
+            (PaymentMethod::Direct, direct_gas)
+        };
+        
+        PaymentMethodRecommendation {
+            counterparty,
+            method,
+            per_payment: expected_volume / U256::from(expected_count.max(1)),
+            estimated_gas,
+        }
+    }
+    
+    /// Get the channel challenge period in seconds
+    pub async fn challenge_period(&self) -> Result<U256> {
+        let period = self.call_with_retry(self.channels.challenge_period()).await?;
+        Ok(period)
+    }
+    
+    /// Lead time this agent should keep before a challenge deadline, from
+    /// the measured block time, configured confirmations and RPC latency;
+    /// see `challenge_buffer`.
+    ///
+    /// Fails with `ConfigError` if the buffer does not fit in the channel's
+    /// challenge period, since challenges could then not be answered safely.
+    pub async fn safe_challenge_buffer(&self) -> Result<Duration> {
+        let started = Instant::now();
+        self.provider.provider().get_block_number().await?;
+        let rpc_latency = started.elapsed();
+        
+        let (block_time, period) = futures::try_join!(
+            self.measure_block_time(20),
+            self.challenge_period(),
+        )?;
+        
+        let buffer = challenge_buffer(block_time, self.config.confirmations, rpc_latency);
+        if U256::from(buffer.as_secs()) >= period {
+            return Err(SynapseError::ConfigError(format!(
+                "Challenge buffer of {}s does not fit in the {}s challenge period",
+                buffer.as_secs(),
+                period
+            )));
+        }
+        
+        Ok(buffer)
+    }
+    
+    /// Number of payments of `per_payment` above which a channel is cheaper
+    /// than direct router payments.
+    ///
+    /// Compares channel open + close gas against router fee + `pay` gas per
+    /// payment, using the `*_GAS` constants as gas estimates. Everything is
+    /// costed in SYNX wei: `gas_price_in_synx` is the price of one unit of gas
+    /// expressed in SYNX wei (the native gas price in wei times the
+    /// SYNX-per-native-token exchange rate), which the caller must supply.
+    pub async fn channel_breakeven(&self, per_payment: U256, gas_price_in_synx: U256) -> Result<u64> {
+        let fee = protocol_fee(per_payment, self.fee_bps().await?);
+        let direct_cost = fee.saturating_add(gas_price_in_synx.saturating_mul(U256::from(PAY_GAS)));
+        let channel_cost = gas_price_in_synx.saturating_mul(U256::from(CHANNEL_OPEN_GAS + CHANNEL_CLOSE_GAS));
+        
+        Ok(breakeven_payments(channel_cost, direct_cost))
+    }
+    
+    /// Chain and `PaymentChannel` contract this client's channel states are bound to
+    pub fn channel_domain(&self) -> ChannelDomain {
+        ChannelDomain { chain_id: self.config.chain_id, contract: self.config.contracts.payment_channel }
+    }
+    
+    /// Verify many channel-state signatures in parallel; see
+    /// `verify_channel_state_signatures`
+    pub fn verify_signatures(&self, items: &[ChannelSignatureItem]) -> Vec<bool> {
+        verify_channel_state_signatures(self.channel_domain(), items)
+    }
+    
+    /// Sign channel state (EIP-191, as `PaymentChannel` verifies it)
+    pub fn sign_channel_state(
+        &self,
+        channel_id: [u8; 32],
+        balance1: impl Into<U256>,
+        balance2: impl Into<U256>,
+        nonce: U256,
+    ) -> Result<Bytes> {
+        let (balance1, balance2) = (balance1.into(), balance2.into());
+        let hash = channel_state_hash(self.channel_domain(), channel_id, balance1, balance2, nonce);
+        let signature = self.wallet.sign_hash(channel_state_digest(hash))
+            .map_err(|e| SynapseError::WalletError(e))?;
+        
+        Ok(signature.to_vec().into())
+    }
+    
+    // ==================== Calldata Functions ====================
+    //
+    // ABI-encoded calldata for each write operation, for integrators that
+    // submit through their own relayer, bundler or signer. Targets are the
+    // contracts in `config.contracts` named in each method's doc.
+    
+    /// `pay` calldata for the payment router
+    pub fn encode_pay(
+        &self,
+        recipient: Address,
+        amount: impl Into<U256>,
+        payment_id: [u8; 32],
         metadata: Option<Bytes>,
-    ) -> Result<PaymentResult> {
-        let payment_id = self.generate_payment_id("pay");
-        let meta = metadata.unwrap_or_default();
+    ) -> Bytes {
+        encoded(self.router.pay(recipient, amount.into(), payment_id, metadata.unwrap_or_default()))
+    }
+    
+    /// `batchPay` calldata for the payment router
+    pub fn encode_batch_pay(
+        &self,
+        recipients: Vec<Address>,
+        amounts: Vec<U256>,
+        payment_ids: Vec<[u8; 32]>,
+        metadata: Vec<Bytes>,
+    ) -> Bytes {
+        encoded(self.router.batch_pay(recipients, amounts, payment_ids, metadata))
+    }
+    
+    /// `createEscrow` calldata for the payment router
+    pub fn encode_create_escrow(
+        &self,
+        escrow_id: [u8; 32],
+        recipient: Address,
+        arbiter: Address,
+        amount: impl Into<U256>,
+        deadline: U256,
+        metadata: Option<Bytes>,
+    ) -> Bytes {
+        encoded(self.router.create_escrow(
+            recipient,
+            arbiter,
+            amount.into(),
+            deadline,
+            escrow_id,
+            metadata.unwrap_or_default(),
+        ))
+    }
+    
+    /// `releaseEscrow` calldata for the payment router
+    pub fn encode_release_escrow(&self, escrow_id: [u8; 32]) -> Bytes {
+        encoded(self.router.release_escrow(escrow_id))
+    }
+    
+    /// `refundEscrow` calldata for the payment router
+    pub fn encode_refund_escrow(&self, escrow_id: [u8; 32]) -> Bytes {
+        encoded(self.router.refund_escrow(escrow_id))
+    }
+    
+    /// `createStream` calldata for the payment router
+    pub fn encode_create_stream(
+        &self,
+        stream_id: [u8; 32],
+        recipient: Address,
+        total_amount: impl Into<U256>,
+        start_time: U256,
+        end_time: U256,
+    ) -> Bytes {
+        encoded(self.router.create_stream(recipient, total_amount.into(), start_time, end_time, stream_id))
+    }
+    
+    /// `cancelStream` calldata for the payment router
+    pub fn encode_cancel_stream(&self, stream_id: [u8; 32]) -> Bytes {
+        encoded(self.router.cancel_stream(stream_id))
+    }
+    
+    /// `withdrawFromStream` calldata for the payment router
+    pub fn encode_withdraw_from_stream(&self, stream_id: [u8; 32]) -> Bytes {
+        encoded(self.router.withdraw_from_stream(stream_id))
+    }
+    
+    /// `approve` calldata for the SYNX token
+    pub fn encode_approve(&self, spender: Address, amount: impl Into<U256>) -> Bytes {
+        encoded(self.token.approve(spender, amount.into()))
+    }
+    
+    /// `transfer` calldata for the SYNX token
+    pub fn encode_transfer(&self, to: Address, amount: impl Into<U256>) -> Bytes {
+        encoded(self.token.transfer(to, amount.into()))
+    }
+    
+    /// `registerAgent` calldata for the reputation registry
+    pub fn encode_register_agent(&self, name: &str, metadata_uri: &str, stake: impl Into<U256>) -> Bytes {
+        encoded(self.reputation.register_agent(name.to_string(), metadata_uri.to_string(), stake.into()))
+    }
+    
+    /// `deregisterAgent` calldata for the reputation registry
+    pub fn encode_deregister_agent(&self) -> Bytes {
+        encoded(self.reputation.deregister_agent())
+    }
+    
+    /// `increaseStake` calldata for the reputation registry
+    pub fn encode_increase_stake(&self, amount: impl Into<U256>) -> Bytes {
+        encoded(self.reputation.increase_stake(amount.into()))
+    }
+    
+    /// `decreaseStake` calldata for the reputation registry
+    pub fn encode_decrease_stake(&self, amount: impl Into<U256>) -> Bytes {
+        encoded(self.reputation.decrease_stake(amount.into()))
+    }
+    
+    /// `registerService` calldata for the service registry
+    pub fn encode_register_service(
+        &self,
+        name: &str,
+        category: &str,
+        description: &str,
+        endpoint: &str,
+        base_price: impl Into<U256>,
+        pricing_model: PricingModel,
+    ) -> Bytes {
+        let base_price = base_price.into();
+        encoded(self.services.register_service(
+            name.to_string(),
+            category.to_string(),
+            description.to_string(),
+            endpoint.to_string(),
+            base_price,
+            pricing_model as u8,
+        ))
+    }
+    
+    /// `updateService` calldata for the service registry
+    pub fn encode_update_service(
+        &self,
+        service_id: [u8; 32],
+        description: &str,
+        endpoint: &str,
+        base_price: impl Into<U256>,
+    ) -> Bytes {
+        let base_price = base_price.into();
+        encoded(self.services.update_service(service_id, description.to_string(), endpoint.to_string(), base_price))
+    }
+    
+    /// `deactivateService` calldata for the service registry
+    pub fn encode_deactivate_service(&self, service_id: [u8; 32]) -> Bytes {
+        encoded(self.services.deactivate_service(service_id))
+    }
+    
+    /// `activateService` calldata for the service registry
+    pub fn encode_activate_service(&self, service_id: [u8; 32]) -> Bytes {
+        encoded(self.services.activate_service(service_id))
+    }
+    
+    /// `openChannel` calldata for the payment channel contract
+    pub fn encode_open_channel(
+        &self,
+        counterparty: Address,
+        my_deposit: impl Into<U256>,
+        their_deposit: impl Into<U256>,
+    ) -> Bytes {
+        encoded(self.channels.open_channel(counterparty, my_deposit.into(), their_deposit.into()))
+    }
+    
+    /// `fundChannel` calldata for the payment channel contract
+    pub fn encode_fund_channel(&self, channel_id: [u8; 32], amount: impl Into<U256>) -> Bytes {
+        encoded(self.channels.fund_channel(channel_id, amount.into()))
+    }
+    
+    /// `cooperativeClose` calldata for the payment channel contract
+    pub fn encode_cooperative_close(
+        &self,
+        counterparty: Address,
+        balance1: impl Into<U256>,
+        balance2: impl Into<U256>,
+        nonce: U256,
+        sig1: Bytes,
+        sig2: Bytes,
+    ) -> Bytes {
+        let (balance1, balance2) = (balance1.into(), balance2.into());
+        encoded(self.channels.cooperative_close(counterparty, balance1, balance2, nonce, sig1, sig2))
+    }
+    
+    /// `initiateClose` calldata for the payment channel contract
+    pub fn encode_initiate_close(
+        &self,
+        counterparty: Address,
+        balance1: impl Into<U256>,
+        balance2: impl Into<U256>,
+        nonce: U256,
+        sig1: Bytes,
+        sig2: Bytes,
+    ) -> Bytes {
+        let (balance1, balance2) = (balance1.into(), balance2.into());
+        encoded(self.channels.initiate_close(counterparty, balance1, balance2, nonce, sig1, sig2))
+    }
+    
+    /// `challengeClose` calldata for the payment channel contract
+    pub fn encode_challenge_close(
+        &self,
+        counterparty: Address,
+        balance1: impl Into<U256>,
+        balance2: impl Into<U256>,
+        nonce: U256,
+        sig1: Bytes,
+        sig2: Bytes,
+    ) -> Bytes {
+        let (balance1, balance2) = (balance1.into(), balance2.into());
+        encoded(self.channels.challenge_close(counterparty, balance1, balance2, nonce, sig1, sig2))
+    }
+    
+    /// `finalizeClose` calldata for the payment channel contract
+    pub fn encode_finalize_close(&self, counterparty: Address) -> Bytes {
+        encoded(self.channels.finalize_close(counterparty))
+    }
+    
+    // ==================== Encryption Functions ====================
+    
+    /// Uncompressed secp256k1 public key (65 bytes) that counterparties use
+    /// to encrypt metadata for this agent
+    pub fn encryption_public_key(&self) -> Bytes {
+        use ethers::core::k256::elliptic_curve::sec1::ToEncodedPoint;
+        
+        let point = self.wallet.signer().verifying_key().to_encoded_point(false);
+        Bytes::from(point.as_bytes().to_vec())
+    }
+    
+    /// Encrypt payment metadata so only the holder of `recipient_pubkey` can read it.
+    ///
+    /// Scheme (ECIES, compatible with eciesjs / eciespy): an ephemeral
+    /// secp256k1 key is generated, the ECDH shared point is expanded with
+    /// HKDF-SHA256 into an AES-256-GCM key, and the output is
+    /// `ephemeral_pubkey (65) || nonce (16) || tag (16) || ciphertext`.
+    /// `recipient_pubkey` may be compressed (33 bytes) or uncompressed (65 bytes).
+    #[cfg(feature = "encryption")]
+    pub fn encrypt_metadata(&self, recipient_pubkey: &[u8], plaintext: &[u8]) -> Result<Bytes> {
+        ecies::encrypt(recipient_pubkey, plaintext)
+            .map(Bytes::from)
+            .map_err(|e| SynapseError::EncryptionError(e.to_string()))
+    }
+    
+    /// Decrypt metadata encrypted to this agent with `encrypt_metadata`
+    #[cfg(feature = "encryption")]
+    pub fn decrypt_metadata(&self, ciphertext: &[u8]) -> Result<Bytes> {
+        let secret = self.wallet.signer().to_bytes();
+        ecies::decrypt(secret.as_slice(), ciphertext)
+            .map(Bytes::from)
+            .map_err(|e| SynapseError::EncryptionError(e.to_string()))
+    }
+    
+    // ==================== Utility Functions ====================
+    
+    /// Fetch and decode logs matching `filter` in `[from_block, to_block]`,
+    /// splitting the range into `LOG_CHUNK_SIZE` requests
+    async fn scan_logs<D: EthLogDecode>(
+        &self,
+        filter: Filter,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<Vec<(D, LogMeta)>> {
+        let mut results = Vec::new();
+        let mut start = from_block;
+        
+        while start <= to_block {
+            let end = start.saturating_add(LOG_CHUNK_SIZE - 1).min(to_block);
+            let chunk = filter.clone().from_block(start).to_block(end);
+            
+            for log in self.provider.provider().get_logs(&chunk).await? {
+                let meta = LogMeta::from(&log);
+                let event = parse_log::<D>(log)
+                    .map_err(|e| SynapseError::ContractError(e.to_string()))?;
+                results.push((event, meta));
+            }
+            
+            start = end + 1;
+        }
+        
+        Ok(results)
+    }
+    
+    /// Run a view call, retrying transient failures with exponential backoff
+    /// and serving recent results from the view cache when enabled.
+    /// Reverts are returned immediately as `CallReverted`.
+    async fn call_with_retry<D>(&self, call: ContractCall<SignerClient, D>) -> Result<D>
+    where
+        D: Detokenize + Clone + Send + Sync + 'static,
+    {
+        let ttl = self.config.view_cache_ttl;
+        let key = format!("{:?}:{:?}:{:?}", call.tx.to(), call.calldata(), call.block);
+        
+        if let Some(ttl) = ttl {
+            if let Ok(cache) = self.view_cache.lock() {
+                if let Some((at, value)) = cache.get(&key) {
+                    if at.elapsed() < ttl {
+                        if let Some(value) = value.downcast_ref::<D>() {
+                            return Ok(value.clone());
+                        }
+                    }
+                }
+            }
+        }
+        
+        let mut attempt = 0;
+        let value = loop {
+            match call.call().await {
+                Ok(value) => break value,
+                Err(e) if !e.is_revert() && attempt < self.config.view_retries => {
+                    attempt += 1;
+                    tokio::time::sleep(Duration::from_millis(100 << attempt.min(6))).await;
+                }
+                Err(e) if e.is_revert() => return Err(SynapseError::CallReverted(e.to_string())),
+                Err(e) => return Err(SynapseError::ContractError(e.to_string())),
+            }
+        };
+        
+        if let Some(ttl) = ttl {
+            if let Ok(mut cache) = self.view_cache.lock() {
+                cache.retain(|_, (at, _)| at.elapsed() < ttl);
+                cache.insert(key, (Instant::now(), Arc::new(value.clone())));
+            }
+        }
+        
+        Ok(value)
+    }
+    
+    /// Drop every cached view result
+    fn clear_view_cache(&self) {
+        self.view_cache.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+    
+    /// Scale estimated fees by the configured gas price multiplier
+    async fn apply_gas_policy(&self, tx: &mut TypedTransaction) -> Result<()> {
+        let multiplier = self.config.gas_price_multiplier;
+        if (multiplier - 1.0).abs() < f64::EPSILON {
+            return Ok(());
+        }
+        
+        match tx {
+            TypedTransaction::Eip1559(inner) if inner.max_fee_per_gas.is_none() => {
+                let (max_fee, priority_fee) = self.provider.provider().estimate_eip1559_fees(None).await?;
+                inner.max_fee_per_gas = Some(scale_u256(max_fee, multiplier));
+                inner.max_priority_fee_per_gas = Some(scale_u256(priority_fee, multiplier));
+            }
+            TypedTransaction::Legacy(inner) if inner.gas_price.is_none() => {
+                let gas_price = self.provider.provider().get_gas_price().await?;
+                inner.gas_price = Some(scale_u256(gas_price, multiplier));
+            }
+            _ => {}
+        }
+        
+        Ok(())
+    }
+    
+    /// Send a contract call and wait for the configured confirmations
+    async fn send_call<D: Detokenize>(&self, call: ContractCall<SignerClient, D>) -> Result<TransactionReceipt> {
+        let tx_hash = self.submit_call(call).await?;
+        self.wait_for_receipt(tx_hash).await
+    }
+    
+    /// Send several calls back to back with sequential nonces, then wait for
+    /// all of them. A failed send does not stop the rest; returns one result
+    /// per call, in order.
+    async fn send_calls_pipelined<D: Detokenize>(
+        &self,
+        calls: Vec<ContractCall<SignerClient, D>>,
+    ) -> Vec<Result<TransactionReceipt>> {
+        let mut submitted = Vec::with_capacity(calls.len());
+        for call in calls {
+            submitted.push(self.submit_call(call).await);
+        }
+        
+        futures::future::join_all(submitted.into_iter().map(|hash| async move {
+            self.wait_for_receipt(hash?).await
+        }))
+        .await
+    }
+    
+    /// Apply the gas policy and nonce to a call and broadcast it
+    async fn submit_call<D: Detokenize>(&self, mut call: ContractCall<SignerClient, D>) -> Result<H256> {
+        if self.config.gas_model == GasModel::Legacy {
+            call = call.legacy();
+        }
+        self.submit_tx(call.tx).await
+    }
+    
+    /// Apply the gas policy and nonce to a transaction and broadcast it
+    async fn submit_tx(&self, mut tx: TypedTransaction) -> Result<H256> {
+        self.apply_gas_policy(&mut tx).await?;
+        
+        if tx.nonce().is_none() {
+            let nonce = self.reserve_nonce().await?;
+            tx.set_nonce(nonce);
+        }
+        
+        match self.provider.send_transaction(tx, None).await {
+            Ok(pending) => {
+                self.clear_view_cache();
+                Ok(pending.tx_hash())
+            }
+            Err(e) => {
+                self.invalidate_nonce().await;
+                Err(SynapseError::ContractError(e.to_string()))
+            }
+        }
+    }
+    
+    /// Wait for a broadcast transaction to reach the configured confirmations
+    async fn wait_for_receipt(&self, tx_hash: H256) -> Result<TransactionReceipt> {
+        let receipt = PendingTransaction::new(tx_hash, self.provider.provider())
+            .confirmations(self.config.confirmations)
+            .await
+            .map_err(|e| SynapseError::TransactionFailed(e.to_string()))?
+            .ok_or(SynapseError::TransactionFailed("No receipt".to_string()))?;
+        // Reads made while the transaction was pending may predate it
+        self.clear_view_cache();
+        
+        if receipt.status != Some(U64::one()) {
+            return Err(SynapseError::TransactionFailed(format!("{:?} reverted", tx_hash)));
+        }
+        Ok(receipt)
+    }
+    
+    /// Generate a unique payment ID
+    fn generate_payment_id(&self, prefix: &str) -> [u8; 32] {
+        use ethers::utils::keccak256;
+        use std::time::{SystemTime, UNIX_EPOCH};
+        
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        
+        let data = format!("{}-{}-{}", prefix, timestamp, self.address());
+        keccak256(data.as_bytes())
+    }
+    
+    /// Deterministic escrow id for `seed`, scoped to this wallet
+    pub fn escrow_id_for(&self, seed: &str) -> [u8; 32] {
+        self.deterministic_id("escrow", seed)
+    }
+    
+    /// Deterministic stream id for `seed`, scoped to this wallet
+    pub fn stream_id_for(&self, seed: &str) -> [u8; 32] {
+        self.deterministic_id("stream", seed)
+    }
+    
+    /// Hash `prefix`, `seed` and the wallet address into a reproducible id
+    fn deterministic_id(&self, prefix: &str, seed: &str) -> [u8; 32] {
+        use ethers::utils::keccak256;
+        
+        let data = format!("{}-{}-{:?}", prefix, seed, self.address());
+        keccak256(data.as_bytes())
+    }
+    
+    /// Parse SYNX amount from string
+    pub fn parse_synx(amount: &str) -> Result<U256> {
+        ethers::utils::parse_ether(amount)
+            .map_err(|e| SynapseError::ConfigError(e.to_string()))
+    }
+    
+    /// Format SYNX amount to string
+    pub fn format_synx(amount: U256) -> String {
+        ethers::utils::format_ether(amount)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_tier_conversion() {
+        assert_eq!(Tier::from(0), Tier::Unverified);
+        assert_eq!(Tier::from(5), Tier::Diamond);
+        assert_eq!(Tier::from(99), Tier::Unverified);
+    }
+    
+    #[test]
+    fn test_funded_error_carries_funding_tx() {
+        let funding_tx = H256::repeat_byte(1);
+        let err = funded(Some(funding_tx))(SynapseError::InvalidSignature);
+        assert!(matches!(
+            err,
+            SynapseError::FundedSpendFailed { funding_tx: tx, source } if tx == funding_tx && matches!(*source, SynapseError::InvalidSignature)
+        ));
+        assert!(matches!(funded(None)(SynapseError::InvalidSignature), SynapseError::InvalidSignature));
+    }
+    
+    #[test]
+    fn test_escrow_status_conversion() {
+        assert_eq!(EscrowStatus::from(0), EscrowStatus::Open);
+        assert_eq!(EscrowStatus::from(3), EscrowStatus::Disputed);
+        assert_eq!(EscrowStatus::from(9), EscrowStatus::Unknown);
+    }
+    
+    #[test]
+    fn test_pricing_model_conversion() {
+        assert_eq!(PricingModel::from(0), PricingModel::PerRequest);
+        assert_eq!(PricingModel::from(4), PricingModel::Subscription);
+    }
+    
+    #[test]
+    fn test_resolve_metadata_uri() {
+        let gateway = "https://ipfs.io/ipfs/";
+        assert_eq!(
+            resolve_metadata_uri("ipfs://QmHash/agent.json", gateway).unwrap(),
+            "https://ipfs.io/ipfs/QmHash/agent.json"
+        );
+        assert_eq!(
+            resolve_metadata_uri("https://example.com/a.json", gateway).unwrap(),
+            "https://example.com/a.json"
+        );
+        assert!(resolve_metadata_uri("ftp://example.com/a.json", gateway).is_err());
+    }
+    
+    #[test]
+    fn test_metadata_document_hash() {
+        let mut doc = AgentMetadata { name: "agent".to_string(), ..Default::default() };
+        doc.extra.insert("b".to_string(), serde_json::json!(1));
+        doc.extra.insert("a".to_string(), serde_json::json!({ "y": 2, "x": 1 }));
+        
+        let mut reordered = AgentMetadata { name: "agent".to_string(), ..Default::default() };
+        reordered.extra.insert("a".to_string(), serde_json::json!({ "x": 1, "y": 2 }));
+        reordered.extra.insert("b".to_string(), serde_json::json!(1));
+        assert_eq!(metadata_document_hash(&doc), metadata_document_hash(&reordered));
+        
+        let uri = metadata_uri_with_hash("ipfs://QmHash/agent.json", &doc);
+        assert_eq!(metadata_uri_hash(&uri), Some(metadata_document_hash(&doc)));
+        assert_eq!(metadata_uri_hash("ipfs://QmHash/agent.json"), None);
+        
+        reordered.description = "changed".to_string();
+        assert_ne!(metadata_document_hash(&doc), metadata_document_hash(&reordered));
+    }
+    
+    #[test]
+    fn test_spend_plan_allowances() {
+        let contracts = ContractAddresses {
+            token: Address::from_low_u64_be(1),
+            payment_router: Address::from_low_u64_be(2),
+            reputation: Address::from_low_u64_be(3),
+            service_registry: Address::from_low_u64_be(4),
+            payment_channel: Address::from_low_u64_be(5),
+        };
+        let plan = SpendPlan {
+            operations: vec![
+                PlannedSpend::Payment(U256::from(100)),
+                PlannedSpend::Escrow(U256::from(300)),
+                PlannedSpend::Stake(U256::from(1000)),
+            ],
+            margin_bps: 1000,
+        };
+        
+        let allowances = plan.allowances(&contracts);
+        assert_eq!(allowances, vec![
+            (contracts.payment_router, U256::from(440)),
+            (contracts.reputation, U256::from(1100)),
+        ]);
+    }
+    
+    #[test]
+    fn test_synx_units() {
+        let amount = Synx::from_synx("10.5").unwrap();
+        assert_eq!(amount.as_wei(), U256::from(10_500_000_000_000_000_000u128));
+        assert_eq!(U256::from(amount), amount.as_wei());
+        assert_eq!(amount.to_string(), "10.5 SYNX");
+        assert_eq!(Synx::from_wei(U256::exp10(18) * 3).to_string(), "3 SYNX");
+    }
+    
+    #[test]
+    fn test_protocol_fee() {
+        assert_eq!(protocol_fee(U256::from(1_000_000), U256::from(10)), U256::from(1_000));
+        assert_eq!(protocol_fee(U256::from(999), U256::from(10)), U256::zero());
+    }
+    
+    #[test]
+    fn test_gross_for_net() {
+        let (fee_bps, discount_bps) = (U256::from(50), U256::from(2500));
+        
+        for net in [0u64, 1, 999, 1_000_000, 123_456_789] {
+            let net = U256::from(net);
+            let gross = gross_for_net(net, fee_bps, discount_bps).unwrap();
+            assert_eq!(gross - discounted_fee(gross, fee_bps, discount_bps), net);
+            assert!(gross.is_zero() || gross - 1 - discounted_fee(gross - 1, fee_bps, discount_bps) < net);
+        }
+        
+        assert_eq!(gross_for_net(U256::one(), U256::from(FEE_DENOMINATOR), U256::zero()), None);
+        assert_eq!(gross_for_net(U256::MAX, fee_bps, discount_bps), None);
+        assert_eq!(gross_for_net(U256::MAX / FEE_DENOMINATOR, U256::from(9_999), U256::zero()), None);
+    }
+    
+    #[test]
+    fn test_event_checkpoint_ordering() {
+        let checkpoint = EventCheckpoint::new(100, 3);
+        assert!(EventCheckpoint::new(100, 4) > checkpoint);
+        assert!(EventCheckpoint::new(101, 0) > checkpoint);
+        assert!(EventCheckpoint::new(99, 10) < checkpoint);
+        
+        let json = serde_json::to_string(&checkpoint).unwrap();
+        assert_eq!(serde_json::from_str::<EventCheckpoint>(&json).unwrap(), checkpoint);
+    }
+    
+    #[test]
+    fn test_scale_u256() {
+        assert_eq!(scale_u256(U256::from(100), 1.0), U256::from(100));
+        assert_eq!(scale_u256(U256::from(100), 1.25), U256::from(125));
+    }
+    
+    #[test]
+    fn test_batch_audit_compare() {
+        let a = Address::from_low_u64_be(1);
+        let b = Address::from_low_u64_be(2);
+        let c = Address::from_low_u64_be(3);
+        let d = Address::from_low_u64_be(4);
+        
+        let expected = [(a, U256::from(10)), (b, U256::from(20)), (c, U256::from(30))];
+        let actual = [(a, U256::from(10)), (b, U256::from(25)), (d, U256::from(40))];
+        
+        let audit = BatchAudit::compare(&expected, &actual);
+        assert_eq!(audit.missing, vec![(c, U256::from(30))]);
+        assert_eq!(audit.extra, vec![(d, U256::from(40))]);
+        assert_eq!(audit.mismatched, vec![(b, U256::from(20), U256::from(25))]);
+        assert!(!audit.is_clean());
+        assert!(BatchAudit::compare(&expected, &expected).is_clean());
+    }
+    
+    fn test_wallet() -> LocalWallet {
+        "0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318"
+            .parse::<LocalWallet>()
+            .unwrap()
+    }
+    
+    fn test_counterparty() -> LocalWallet {
+        "0x59c6995e998f97a5a0044966f0945389dc9e86dae88c7a8412f4603b6b78690d"
+            .parse::<LocalWallet>()
+            .unwrap()
+    }
+    
+    fn test_domain() -> ChannelDomain {
+        ChannelDomain { chain_id: 8453, contract: Address::from_low_u64_be(0xc4a) }
+    }
+    
+    fn signed_state(wallet: &LocalWallet, nonce: u64) -> SignedChannelState {
+        let mut state = SignedChannelState {
+            domain: test_domain(),
+            channel_id: [7u8; 32],
+            balance1: U256::from(60),
+            balance2: U256::from(40),
+            nonce: U256::from(nonce),
+            signatures: Vec::new(),
+        };
+        state.sign(wallet).unwrap();
+        state
+    }
+    
+    #[test]
+    fn test_cooperative_close_signature() {
+        let (wallet, counterparty) = (test_wallet(), test_counterparty());
+        let state = signed_state(&wallet, 3);
         
-        let tx = self.router
-            .pay(recipient, amount, payment_id.into(), meta)
-            .send()
-            .await
-            .map_err(|e| SynapseError::ContractError(e.to_string()))?;
+        let sig = state.sign_cooperative_close(&counterparty).unwrap();
+        assert!(state.verify_cooperative_close(counterparty.address(), &sig));
+        assert!(!state.verify_cooperative_close(wallet.address(), &sig));
         
-        let receipt = tx.await
-            .map_err(|e| SynapseError::TransactionFailed(e.to_string()))?
-            .ok_or(SynapseError::TransactionFailed("No receipt".to_string()))?;
+        // A state signature does not authorize a cooperative close
+        let state_sig = state.signature_of(wallet.address()).unwrap();
+        assert!(!state.verify_cooperative_close(wallet.address(), state_sig));
+    }
+    
+    #[test]
+    fn test_channel_state_hash_is_domain_bound() {
+        let wallet = test_wallet();
+        let state = signed_state(&wallet, 1);
+        assert!(state.verify_signatures());
         
-        Ok(PaymentResult {
-            tx_hash: receipt.transaction_hash,
-            payment_id: payment_id.into(),
-            amount,
-            fee: U256::zero(), // Would need to parse from events
-        })
+        let other_chain = SignedChannelState { domain: ChannelDomain { chain_id: 1, ..test_domain() }, ..state.clone() };
+        assert!(!other_chain.verify_signatures());
+        
+        // Raw (unprefixed) signatures are not what the contract recovers
+        let raw = wallet.sign_hash(H256::from(state.hash())).unwrap();
+        let unprefixed = SignedChannelState { signatures: vec![(wallet.address(), raw.to_vec().into())], ..state };
+        assert!(!unprefixed.verify_signatures());
     }
     
-    /// Send batch payments
-    pub async fn batch_pay(
-        &self,
-        recipients: Vec<Address>,
-        amounts: Vec<U256>,
-    ) -> Result<H256> {
-        let payment_ids: Vec<[u8; 32]> = recipients
-            .iter()
-            .enumerate()
-            .map(|(i, _)| self.generate_payment_id(&format!("batch-{}", i)))
-            .collect();
+    #[test]
+    fn test_coalesce_channel_states() {
+        let (wallet, counterparty) = (test_wallet(), test_counterparty());
+        let (p1, p2) = (wallet.address(), counterparty.address());
+        let both = |nonce| {
+            let mut state = signed_state(&wallet, nonce);
+            state.sign(&counterparty).unwrap();
+            state
+        };
         
-        let metadata: Vec<Bytes> = vec![Bytes::default(); recipients.len()];
+        let updates: Vec<SignedChannelState> = (1..=5).map(both).collect();
+        assert_eq!(coalesce_channel_states(p1, p2, updates).unwrap().nonce, U256::from(5));
         
-        let tx = self.router
-            .batch_pay(recipients, amounts, payment_ids, metadata)
-            .send()
-            .await
-            .map_err(|e| SynapseError::ContractError(e.to_string()))?;
+        let regressed = vec![both(2), both(1)];
+        assert!(matches!(coalesce_channel_states(p1, p2, regressed), Err(SynapseError::StaleState { .. })));
         
-        let receipt = tx.await
-            .map_err(|e| SynapseError::TransactionFailed(e.to_string()))?
-            .ok_or(SynapseError::TransactionFailed("No receipt".to_string()))?;
+        let mut tampered = vec![both(1), both(2)];
+        tampered[0].balance1 = U256::from(50);
+        tampered[0].balance2 = U256::from(50);
+        assert!(matches!(coalesce_channel_states(p1, p2, tampered), Err(SynapseError::InvalidSignature)));
         
-        Ok(receipt.transaction_hash)
+        let one_sided = vec![both(1), signed_state(&wallet, 2)];
+        assert!(matches!(coalesce_channel_states(p1, p2, one_sided), Err(SynapseError::InvalidSignature)));
+        
+        let mut overflowing = both(1);
+        overflowing.balance1 = U256::MAX;
+        assert!(matches!(coalesce_channel_states(p1, p2, vec![overflowing]), Err(SynapseError::InvalidInput(_))));
+        
+        assert!(coalesce_channel_states(p1, p2, Vec::new()).is_err());
     }
     
-    /// Create an escrow
-    pub async fn create_escrow(
-        &self,
-        recipient: Address,
-        arbiter: Address,
-        amount: U256,
-        deadline: U256,
-    ) -> Result<H256> {
-        let escrow_id = self.generate_payment_id("escrow");
+    #[test]
+    fn test_validate_channel_capacity() {
+        let payments = [U256::from(30), U256::from(50)];
         
-        let tx = self.router
-            .create_escrow(recipient, arbiter, amount, deadline, escrow_id.into(), Bytes::default())
-            .send()
-            .await
-            .map_err(|e| SynapseError::ContractError(e.to_string()))?;
+        assert!(validate_channel_capacity(U256::from(80), &payments).is_ok());
+        assert!(matches!(
+            validate_channel_capacity(U256::from(79), &payments),
+            Err(SynapseError::InsufficientBalance { required, .. }) if required == U256::from(80)
+        ));
+    }
+    
+    #[test]
+    fn test_validate_stream_times() {
+        let now = U256::from(1_700_000_000u64);
+        let window = Duration::from_secs(300);
         
-        let receipt = tx.await
-            .map_err(|e| SynapseError::TransactionFailed(e.to_string()))?
-            .ok_or(SynapseError::TransactionFailed("No receipt".to_string()))?;
+        assert!(validate_stream_times(now, now + 3600, now, window).is_ok());
+        assert!(validate_stream_times(now - 300, now + 3600, now, window).is_ok());
+        assert!(validate_stream_times(now + 3600, now, now, window).is_err());
+        assert!(validate_stream_times(now, now, now, window).is_err());
+        assert!(validate_stream_times(now - 301, now + 3600, now, window).is_err());
+        // Milliseconds instead of seconds
+        assert!(validate_stream_times(now * 1000, now * 1000 + 3_600_000, now, window).is_err());
+    }
+    
+    #[test]
+    fn test_challenge_buffer() {
+        // (12s * 3 + 0.5s) * 2
+        let buffer = challenge_buffer(Duration::from_secs(12), 2, Duration::from_millis(500));
+        assert_eq!(buffer, Duration::from_secs(73));
         
-        Ok(receipt.transaction_hash)
+        assert_eq!(challenge_buffer(Duration::from_secs(2), 1, Duration::ZERO), CHALLENGE_BUFFER_MIN);
     }
     
-    /// Create a payment stream
-    pub async fn create_stream(
-        &self,
-        recipient: Address,
-        total_amount: U256,
-        start_time: U256,
-        end_time: U256,
-    ) -> Result<StreamResult> {
-        let stream_id = self.generate_payment_id("stream");
+    #[test]
+    fn test_close_risk() {
+        let (wallet, counterparty) = (test_wallet(), test_counterparty());
         
-        let tx = self.router
-            .create_stream(recipient, total_amount, start_time, end_time, stream_id.into())
-            .send()
-            .await
-            .map_err(|e| SynapseError::ContractError(e.to_string()))?;
+        let mut state = signed_state(&wallet, 5);
+        assert_eq!(CloseRisk::assess(&state, None), CloseRisk::Invalid);
         
-        let receipt = tx.await
-            .map_err(|e| SynapseError::TransactionFailed(e.to_string()))?
-            .ok_or(SynapseError::TransactionFailed("No receipt".to_string()))?;
+        state.sign(&counterparty).unwrap();
+        assert_eq!(CloseRisk::assess(&state, None), CloseRisk::Unknown);
+        assert_eq!(CloseRisk::assess(&state, Some(U256::from(5))), CloseRisk::Low);
+        assert_eq!(CloseRisk::assess(&state, Some(U256::from(6))), CloseRisk::High);
+    }
+    
+    #[test]
+    fn test_channel_session_rejects_regression() {
+        let (wallet, counterparty) = (test_wallet(), test_counterparty());
+        let both = |nonce| {
+            let mut state = signed_state(&wallet, nonce);
+            state.sign(&counterparty).unwrap();
+            state
+        };
+        let mut session = ChannelSession::new();
+        assert!(matches!(session.accept(&both(1)), Err(SynapseError::ChannelNotFound)));
+        session.register([7u8; 32], wallet.address(), counterparty.address());
         
-        Ok(StreamResult {
-            tx_hash: receipt.transaction_hash,
-            stream_id: stream_id.into(),
-            total_amount,
-            start_time,
-            end_time,
-        })
+        session.accept(&both(1)).unwrap();
+        session.accept(&both(2)).unwrap();
+        assert!(matches!(session.accept(&both(2)), Err(SynapseError::StaleState { .. })));
+        assert_eq!(session.latest_nonce([7u8; 32]), Some(U256::from(2)));
+        
+        let mut forged = both(3);
+        forged.balance1 = U256::from(100);
+        assert!(matches!(session.accept(&forged), Err(SynapseError::InvalidSignature)));
+        
+        // A third party signing alone cannot advance the channel
+        let outsider = "0x5de4111afa1a4b94908f83103eb1f1706367c2e68ca870fc3fb9a804cdab365a"
+            .parse::<LocalWallet>()
+            .unwrap();
+        let poisoned = signed_state(&outsider, u64::MAX);
+        assert!(matches!(session.accept(&poisoned), Err(SynapseError::InvalidSignature)));
+        assert!(matches!(session.accept(&signed_state(&wallet, 4)), Err(SynapseError::InvalidSignature)));
+        assert_eq!(session.latest_nonce([7u8; 32]), Some(U256::from(2)));
     }
     
-    // ==================== Agent Functions ====================
+    #[test]
+    fn test_channel_heartbeat_staleness() {
+        let mut heartbeat = ChannelHeartbeat::new();
+        let channel_id = [7u8; 32];
+        assert!(!heartbeat.is_counterparty_stale(channel_id, Duration::ZERO));
+        
+        heartbeat.record_activity(channel_id);
+        assert!(!heartbeat.is_counterparty_stale(channel_id, Duration::from_secs(3600)));
+        
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(heartbeat.is_counterparty_stale(channel_id, Duration::from_millis(1)));
+        assert_eq!(heartbeat.stale_channels(Duration::from_millis(1)), vec![channel_id]);
+        
+        heartbeat.forget(channel_id);
+        assert!(heartbeat.stale_channels(Duration::ZERO).is_empty());
+    }
     
-    /// Register as an AI agent
-    pub async fn register_agent(
-        &self,
-        name: &str,
-        metadata_uri: &str,
-        stake: U256,
-    ) -> Result<H256> {
-        let tx = self.reputation
-            .register_agent(name.to_string(), metadata_uri.to_string(), stake)
-            .send()
-            .await
-            .map_err(|e| SynapseError::ContractError(e.to_string()))?;
+    #[test]
+    fn test_capabilities_intersect() {
+        let counterparty = Capabilities {
+            version: 1,
+            pricing_models: vec![PricingModel::PerToken, PricingModel::PerRequest],
+            channels: false,
+            streams: true,
+            escrow: true,
+            metadata_schemas: vec!["tip/1".to_string()],
+        };
         
-        let receipt = tx.await
-            .map_err(|e| SynapseError::TransactionFailed(e.to_string()))?
-            .ok_or(SynapseError::TransactionFailed("No receipt".to_string()))?;
+        let session = Capabilities::sdk().intersect(&counterparty);
+        assert!(session.is_viable());
+        assert_eq!(session.pricing_model, Some(PricingModel::PerRequest));
+        assert_eq!(session.pricing_models, vec![PricingModel::PerRequest, PricingModel::PerToken]);
+        assert!(!session.channels);
+        assert_eq!(session.metadata_schema.as_deref(), Some("tip/1"));
         
-        Ok(receipt.transaction_hash)
+        let none = Capabilities { pricing_models: vec![], ..counterparty };
+        assert!(!Capabilities::sdk().intersect(&none).is_viable());
     }
     
-    /// Get agent information
-    pub async fn get_agent(&self, address: Address) -> Result<AgentInfo> {
-        let agent = self.reputation.agents(address).call().await
-            .map_err(|e| SynapseError::ContractError(e.to_string()))?;
+    #[test]
+    fn test_settlement_summary() {
+        let wallet = test_wallet();
+        let state = |balance1: u64, balance2: u64, nonce: u64| {
+            let mut state = SignedChannelState {
+                domain: test_domain(),
+                channel_id: [7u8; 32],
+                balance1: U256::from(balance1),
+                balance2: U256::from(balance2),
+                nonce: U256::from(nonce),
+                signatures: vec![],
+            };
+            state.sign(&wallet).unwrap();
+            state
+        };
         
-        let tier = self.reputation.get_tier(address).call().await
-            .map_err(|e| SynapseError::ContractError(e.to_string()))?;
+        let history = vec![state(100, 100, 1), state(70, 130, 2), state(90, 110, 4), state(80, 120, 3)];
+        let final_state = state(140, 110, 5);
         
-        let success_rate = self.reputation.get_success_rate(address).call().await
-            .map_err(|e| SynapseError::ContractError(e.to_string()))?;
+        let mut summary = SettlementSummary::from_states(&final_state, &history).unwrap();
+        assert_eq!(summary.states, 5);
+        assert_eq!(summary.initial_balance1, U256::from(100));
+        // 1 -> 2: 30 (nonce 2); 2 -> 1: 10 (nonce 3) + 10 (nonce 4); deposit of 50 at nonce 5
+        assert_eq!(summary.paid_by_1, U256::from(30));
+        assert_eq!(summary.paid_by_2, U256::from(20));
+        assert_eq!(summary.deposited, U256::from(50));
         
-        Ok(AgentInfo {
-            registered: agent.0,
-            name: agent.1,
-            stake: agent.2,
-            reputation_score: agent.3,
-            total_transactions: agent.4,
-            successful_transactions: agent.5,
-            registered_at: agent.6,
-            metadata_uri: agent.7,
-            tier: Tier::from(tier),
-            success_rate: success_rate.as_u64() as f64 / 100.0,
-        })
+        summary.sign(&wallet).unwrap();
+        assert!(summary.verify());
+        
+        assert!(SettlementSummary::from_states(&state(100, 100, 1), &history).is_err());
+        
+        let conflicting = vec![state(100, 100, 1), state(90, 110, 2), state(110, 90, 2)];
+        assert!(matches!(
+            SettlementSummary::from_states(&final_state, &conflicting),
+            Err(SynapseError::InvalidInput(_))
+        ));
+        
+        let mut unsigned = history.clone();
+        unsigned[1].signatures.clear();
+        assert!(matches!(
+            SettlementSummary::from_states(&final_state, &unsigned),
+            Err(SynapseError::InvalidSignature)
+        ));
+        
+        let huge = |nonce: u64| {
+            let mut s = state(0, 0, nonce);
+            s.balance1 = U256::MAX;
+            s.balance2 = U256::one();
+            s.sign(&wallet).unwrap();
+            s
+        };
+        assert!(matches!(
+            SettlementSummary::from_states(&huge(6), &history),
+            Err(SynapseError::InvalidInput(_))
+        ));
     }
     
-    /// Increase stake
-    pub async fn increase_stake(&self, amount: U256) -> Result<H256> {
-        let tx = self.reputation.increase_stake(amount).send().await
-            .map_err(|e| SynapseError::ContractError(e.to_string()))?;
-        
-        let receipt = tx.await
-            .map_err(|e| SynapseError::TransactionFailed(e.to_string()))?
-            .ok_or(SynapseError::TransactionFailed("No receipt".to_string()))?;
+    #[test]
+    fn test_trust_graph_page_rank() {
+        let (a, b, c) = (Address::from_low_u64_be(1), Address::from_low_u64_be(2), Address::from_low_u64_be(3));
+        let edge = |from, to, volume: u64| TrustEdge { from, to, volume: U256::from(volume), count: 1 };
+        let graph = TrustGraph {
+            nodes: vec![a, b, c],
+            edges: vec![edge(a, b, 90), edge(a, c, 10), edge(b, c, 50)],
+        };
         
-        Ok(receipt.transaction_hash)
+        let rank = graph.page_rank(0.85, 50);
+        assert!((rank.values().sum::<f64>() - 1.0).abs() < 1e-9);
+        assert!(rank[&c] > rank[&b] && rank[&b] > rank[&a]);
+        assert!(TrustGraph::default().page_rank(0.85, 10).is_empty());
     }
     
-    // ==================== Service Functions ====================
+    #[test]
+    fn test_breakeven_payments() {
+        assert_eq!(breakeven_payments(U256::from(1000), U256::from(100)), 10);
+        assert_eq!(breakeven_payments(U256::from(1050), U256::from(100)), 10);
+        assert_eq!(breakeven_payments(U256::from(1000), U256::zero()), u64::MAX);
+    }
     
-    /// Register a service
-    pub async fn register_service(
-        &self,
-        name: &str,
-        category: &str,
-        description: &str,
-        endpoint: &str,
-        base_price: U256,
-        pricing_model: PricingModel,
-    ) -> Result<H256> {
-        let tx = self.services
-            .register_service(
-                name.to_string(),
-                category.to_string(),
-                description.to_string(),
-                endpoint.to_string(),
-                base_price,
-                pricing_model as u8,
-            )
-            .send()
-            .await
-            .map_err(|e| SynapseError::ContractError(e.to_string()))?;
+    #[test]
+    fn test_invoice_sign_and_verify() {
+        let wallet = test_wallet();
+        let mut invoice = Invoice {
+            id: "INV-1".to_string(),
+            provider: wallet.address(),
+            line_items: vec![
+                InvoiceLineItem { description: "inference".to_string(), quantity: U256::from(3), unit_price: U256::from(5) },
+                InvoiceLineItem { description: "storage".to_string(), quantity: U256::from(1), unit_price: U256::from(10) },
+            ],
+            signature: Bytes::default(),
+        };
         
-        let receipt = tx.await
-            .map_err(|e| SynapseError::TransactionFailed(e.to_string()))?
-            .ok_or(SynapseError::TransactionFailed("No receipt".to_string()))?;
+        assert_eq!(invoice.total(), U256::from(25));
+        assert!(!invoice.verify());
+        invoice.sign(&wallet).unwrap();
+        assert!(invoice.verify());
         
-        Ok(receipt.transaction_hash)
+        invoice.line_items[0].quantity = U256::from(4);
+        assert!(!invoice.verify());
     }
     
-    /// Get service information
-    pub async fn get_service(&self, service_id: [u8; 32]) -> Result<ServiceInfo> {
-        let service = self.services.services(service_id).call().await
-            .map_err(|e| SynapseError::ContractError(e.to_string()))?;
+    #[test]
+    fn test_delivery_proof_sign_and_verify() {
+        let wallet = test_wallet();
+        let mut proof = DeliveryProof {
+            payment_id: H256::from([1u8; 32]),
+            result_hash: H256::from(delivery_result_hash(b"{\"answer\":42}")),
+            provider: wallet.address(),
+            signature: Bytes::default(),
+        };
         
-        Ok(ServiceInfo {
-            provider: service.0,
-            name: service.1,
-            category: service.2,
-            description: service.3,
-            endpoint: service.4,
-            base_price: service.5,
-            pricing_model: PricingModel::from(service.6),
-            active: service.7,
-            total_requests: service.8,
-            total_revenue: service.9,
-            created_at: service.10,
+        proof.sign(&wallet).unwrap();
+        assert!(proof.verify());
+        
+        proof.result_hash = H256::from(delivery_result_hash(b"{\"answer\":41}"));
+        assert!(!proof.verify());
+    }
+    
+    fn default_tier_requirements() -> Vec<TierRequirement> {
+        let synx = U256::exp10(18);
+        [
+            (Tier::Unverified, 0u64, 0u64, U256::zero()),
+            (Tier::Bronze, 100, 9500, U256::zero()),
+            (Tier::Silver, 1000, 9700, synx * 100),
+            (Tier::Gold, 10000, 9900, synx * 1000),
+        ]
+        .into_iter()
+        .map(|(tier, txs, rate, stake)| TierRequirement {
+            tier,
+            min_transactions: U256::from(txs),
+            min_success_rate_bps: U256::from(rate),
+            min_stake: stake,
+            fee_discount_bps: U256::zero(),
         })
+        .collect()
     }
     
-    /// Find services by category
-    pub async fn find_services(&self, category: &str) -> Result<Vec<[u8; 32]>> {
-        let services = self.services
-            .get_services_by_category(category.to_string())
-            .call()
-            .await
-            .map_err(|e| SynapseError::ContractError(e.to_string()))?;
+    #[test]
+    fn test_project_tier() {
+        let reqs = default_tier_requirements();
+        let stake = U256::exp10(18) * 100;
         
-        Ok(services)
+        assert_eq!(project_tier(U256::from(1000), U256::from(980), stake, &reqs), Tier::Silver);
+        // 96.9% misses Silver's 97% but still meets Bronze's 95%
+        assert_eq!(project_tier(U256::from(1000), U256::from(969), stake, &reqs), Tier::Bronze);
+        assert_eq!(project_tier(U256::from(50), U256::from(50), stake, &reqs), Tier::Unverified);
+        assert_eq!(project_tier(U256::zero(), U256::zero(), stake, &reqs), Tier::Unverified);
     }
     
-    /// Calculate service price
-    pub async fn calculate_price(&self, service_id: [u8; 32], quantity: U256) -> Result<U256> {
-        let price = self.services
-            .calculate_price(service_id, quantity)
-            .call()
-            .await
-            .map_err(|e| SynapseError::ContractError(e.to_string()))?;
+    #[test]
+    fn test_stream_vesting_time() {
+        let total = U256::from(1000);
+        let (start, end) = (U256::from(100), U256::from(200));
         
-        Ok(price)
+        assert_eq!(stream_vesting_time(total, start, end, U256::zero()), Some(start));
+        assert_eq!(stream_vesting_time(total, start, end, U256::from(500)), Some(U256::from(150)));
+        // 10 per second, so 501 first accrues one second later
+        assert_eq!(stream_vesting_time(total, start, end, U256::from(501)), Some(U256::from(151)));
+        assert_eq!(stream_vesting_time(total, start, end, total), Some(end));
+        assert_eq!(stream_vesting_time(total, start, end, total + 1), None);
     }
     
-    // ==================== Channel Functions ====================
-    
-    /// Open a payment channel
-    pub async fn open_channel(
-        &self,
-        counterparty: Address,
-        my_deposit: U256,
-        their_deposit: U256,
-    ) -> Result<H256> {
-        let tx = self.channels
-            .open_channel(counterparty, my_deposit, their_deposit)
-            .send()
-            .await
-            .map_err(|e| SynapseError::ContractError(e.to_string()))?;
-        
-        let receipt = tx.await
-            .map_err(|e| SynapseError::TransactionFailed(e.to_string()))?
-            .ok_or(SynapseError::TransactionFailed("No receipt".to_string()))?;
+    #[test]
+    fn test_optimal_stake_for() {
+        let reqs = default_tier_requirements();
+        let synx = U256::exp10(18);
+        let (total, successful) = (U256::from(1000), U256::from(980));
         
-        Ok(receipt.transaction_hash)
+        // Silver's 5% uplift on 10k SYNX/month dwarfs the cost of 100 SYNX at 10%/yr
+        assert_eq!(optimal_stake_for(total, successful, &reqs, synx * 10, synx * 10_000, 1_000), synx * 100);
+        // Without revenue, any extra stake is pure cost
+        assert_eq!(optimal_stake_for(total, successful, &reqs, synx * 10, U256::zero(), 1_000), synx * 10);
     }
     
-    /// Get channel information
-    pub async fn get_channel(&self, party1: Address, party2: Address) -> Result<ChannelInfo> {
-        let channel_id = self.channels.get_channel_id(party1, party2).call().await
-            .map_err(|e| SynapseError::ContractError(e.to_string()))?;
+    #[test]
+    fn test_churn_risk_score() {
+        let score = U256::from(1000);
+        assert_eq!(churn_risk_score(score, score, 10, 10), 0.0);
+        assert_eq!(churn_risk_score(score, U256::from(1200), 10, 20), 0.0);
+        assert_eq!(churn_risk_score(score, U256::from(500), 10, 5), 0.5);
+        assert_eq!(churn_risk_score(score, U256::zero(), 10, 0), 1.0);
+        assert_eq!(churn_risk_score(score, score, 0, 0), 0.25);
+    }
+    
+    #[test]
+    fn test_request_expected_value() {
+        let impact = |current, projected| FailureImpact {
+            current_tier: current,
+            projected_tier: projected,
+            current_success_rate: 99.0,
+            projected_success_rate: 98.0,
+            current_score: U256::from(500),
+            projected_score: U256::from(490),
+        };
+        let (offered, cost) = (U256::from(1_000), U256::from(200));
         
-        let channel = self.channels.channels(channel_id).call().await
-            .map_err(|e| SynapseError::ContractError(e.to_string()))?;
+        // No tier at stake: 0.9 * 1000 - 200
+        let ev = request_expected_value(offered, cost, 0.1, &impact(Tier::Gold, Tier::Gold));
+        assert!((ev - 700.0).abs() < 1e-9);
         
-        Ok(ChannelInfo {
-            participant1: channel.0,
-            participant2: channel.1,
-            balance1: channel.2,
-            balance2: channel.3,
-            nonce: channel.4,
-            status: ChannelStatus::from(channel.5),
-            challenge_end: channel.6,
-        })
+        // Gold -> Silver loses 500 bps on 100 requests of 1000: 5000 at 10%
+        let ev = request_expected_value(offered, cost, 0.1, &impact(Tier::Gold, Tier::Silver));
+        assert!((ev - 200.0).abs() < 1e-9);
     }
     
-    /// Sign channel state
-    pub fn sign_channel_state(
-        &self,
-        channel_id: [u8; 32],
-        balance1: U256,
-        balance2: U256,
-        nonce: U256,
-    ) -> Result<Bytes> {
-        use ethers::utils::keccak256;
+    #[test]
+    fn test_stake_yield_for() {
+        // Silver adds 500 bps over Bronze: 10_500 earned in a year carries 500 of premium
+        let year = 365 * 24 * 3600;
+        let yield_pct = stake_yield_for(U256::from(10_500), year, Tier::Silver, U256::from(5_000));
+        assert!((yield_pct - 10.0).abs() < 1e-9);
         
-        let mut data = Vec::new();
-        data.extend_from_slice(&channel_id);
-        data.extend_from_slice(&balance1.to_be_bytes::<32>());
-        data.extend_from_slice(&balance2.to_be_bytes::<32>());
-        data.extend_from_slice(&nonce.to_be_bytes::<32>());
+        assert_eq!(stake_yield_for(U256::from(10_500), year, Tier::Bronze, U256::from(5_000)), 0.0);
+        assert_eq!(stake_yield_for(U256::from(10_500), 0, Tier::Silver, U256::from(5_000)), 0.0);
+    }
+    
+    #[test]
+    fn test_escrow_deadline_window() {
+        assert_eq!(escrow_deadline_window(PricingModel::PerRequest, &[]), 3600);
+        assert_eq!(escrow_deadline_window(PricingModel::Subscription, &[]), 30 * 24 * 3600);
         
-        let hash = keccak256(&data);
-        let signature = self.wallet.sign_hash(H256::from(hash))
-            .map_err(|e| SynapseError::WalletError(e))?;
+        // p90 of 1..=10 hours is 10 hours, doubled
+        let history: Vec<u64> = (1..=10).map(|h| h * 3600).collect();
+        assert_eq!(escrow_deadline_window(PricingModel::PerRequest, &history), 20 * 3600);
         
-        Ok(signature.to_vec().into())
+        // Fast history is floored at one hour
+        assert_eq!(escrow_deadline_window(PricingModel::PerRequest, &[5, 10]), 3600);
     }
     
-    // ==================== Utility Functions ====================
+    #[test]
+    fn test_quote_validity_window() {
+        assert_eq!(quote_validity_window(&[]), QUOTE_VALIDITY_MAX);
+        assert_eq!(quote_validity_window(&[1_000]), QUOTE_VALIDITY_MAX);
+        // Intervals of 1h, 2h and 4h: median 2h, halved
+        assert_eq!(quote_validity_window(&[0, 3600, 10_800, 25_200]), Duration::from_secs(3600));
+        // Weekly changes are capped
+        assert_eq!(quote_validity_window(&[0, 604_800]), QUOTE_VALIDITY_MAX);
+    }
     
-    /// Generate a unique payment ID
-    fn generate_payment_id(&self, prefix: &str) -> [u8; 32] {
-        use ethers::utils::keccak256;
-        use std::time::{SystemTime, UNIX_EPOCH};
-        
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_nanos();
+    #[test]
+    fn test_stream_accrued_at() {
+        let total = U256::from(1000);
+        let (start, end) = (U256::from(100), U256::from(200));
         
-        let data = format!("{}-{}-{}", prefix, timestamp, self.address());
-        keccak256(data.as_bytes())
+        assert_eq!(stream_accrued_at(total, start, end, U256::from(50)), U256::zero());
+        assert_eq!(stream_accrued_at(total, start, end, U256::from(125)), U256::from(250));
+        assert_eq!(stream_accrued_at(total, start, end, U256::from(500)), total);
     }
     
-    /// Parse SYNX amount from string
-    pub fn parse_synx(amount: &str) -> Result<U256> {
-        ethers::utils::parse_ether(amount)
-            .map_err(|e| SynapseError::ConfigError(e.to_string()))
+    #[test]
+    fn test_prorated_refund_at_start() {
+        let paid = U256::from(3000);
+        assert_eq!(prorated_refund(paid, U256::from(100), U256::from(400), U256::from(100)), paid);
+        assert_eq!(prorated_refund(paid, U256::from(100), U256::from(400), U256::from(50)), paid);
     }
     
-    /// Format SYNX amount to string
-    pub fn format_synx(amount: U256) -> String {
-        ethers::utils::format_ether(amount)
+    #[test]
+    fn test_prorated_refund_mid_period() {
+        let paid = U256::from(3000);
+        assert_eq!(prorated_refund(paid, U256::from(100), U256::from(400), U256::from(200)), U256::from(2000));
+        // 1/3 used of 1000 leaves 666.67, rounded down
+        assert_eq!(prorated_refund(U256::from(1000), U256::from(0), U256::from(3), U256::from(1)), U256::from(666));
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
     
     #[test]
-    fn test_tier_conversion() {
-        assert_eq!(Tier::from(0), Tier::Unverified);
-        assert_eq!(Tier::from(5), Tier::Diamond);
-        assert_eq!(Tier::from(99), Tier::Unverified);
+    fn test_prorated_refund_at_end() {
+        let paid = U256::from(3000);
+        assert_eq!(prorated_refund(paid, U256::from(100), U256::from(400), U256::from(400)), U256::zero());
+        assert_eq!(prorated_refund(paid, U256::from(100), U256::from(400), U256::from(999)), U256::zero());
     }
     
     #[test]
-    fn test_pricing_model_conversion() {
-        assert_eq!(PricingModel::from(0), PricingModel::PerRequest);
-        assert_eq!(PricingModel::from(4), PricingModel::Subscription);
+    fn test_split_by_weights() {
+        let shares = split_by_weights(U256::from(100), &[1, 1, 1]).unwrap();
+        assert_eq!(shares, vec![U256::from(34), U256::from(33), U256::from(33)]);
+        
+        let shares = split_by_weights(U256::from(1000), &[3, 1]).unwrap();
+        assert_eq!(shares, vec![U256::from(750), U256::from(250)]);
+        
+        assert!(split_by_weights(U256::from(1000), &[]).is_err());
+        assert!(split_by_weights(U256::from(1000), &[1, 0]).is_err());
     }
     
     #[test]
@@ -0,0 +1,226 @@
+//! Scripted end-to-end flows
+//!
+//! Reference integrations and smoke tests against a local or forked node.
+
+use crate::{AgentRole, PricingModel, Result, SignerClient, SynapseClient};
+use ethers::types::{H256, U256};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A step of `run_agent_lifecycle`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LifecycleStep {
+    Approve,
+    Register,
+    Stake,
+    RegisterService,
+    ReceivePayment,
+    Deregister,
+}
+
+/// Service registered during the lifecycle run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServiceSpec {
+    pub name: String,
+    pub category: String,
+    pub description: String,
+    pub endpoint: String,
+    pub base_price: U256,
+    pub pricing_model: PricingModel,
+}
+
+/// Private key that is redacted in `Debug` output and never serialized
+#[derive(Clone)]
+pub struct SecretKey(String);
+
+impl SecretKey {
+    pub fn new(key: impl Into<String>) -> Self {
+        Self(key.into())
+    }
+    
+    fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretKey(<redacted>)")
+    }
+}
+
+/// Payment sent to the agent by a second, funded wallet.
+///
+/// Holds the payer's key, so it is not serializable; `LifecycleSpec`
+/// skips it when serialized.
+#[derive(Debug, Clone)]
+pub struct PaymentSpec {
+    pub payer_private_key: SecretKey,
+    pub amount: U256,
+}
+
+impl PaymentSpec {
+    pub fn new(payer_private_key: &str, amount: U256) -> Self {
+        Self { payer_private_key: SecretKey::new(payer_private_key), amount }
+    }
+}
+
+/// Which lifecycle steps to run and with what parameters.
+///
+/// Every step is optional; disabled steps are left out of the report.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LifecycleSpec {
+    pub agent_name: String,
+    pub metadata_uri: String,
+    /// Stake posted at registration
+    pub stake: U256,
+    /// Approve the registries and register the agent
+    pub register: bool,
+    /// Stake added after registration
+    pub extra_stake: Option<U256>,
+    pub service: Option<ServiceSpec>,
+    #[serde(skip)]
+    pub payment: Option<PaymentSpec>,
+    pub deregister: bool,
+}
+
+impl LifecycleSpec {
+    /// Register with `stake`, then deregister; other steps disabled
+    pub fn new(agent_name: &str, stake: U256) -> Self {
+        Self {
+            agent_name: agent_name.to_string(),
+            metadata_uri: String::new(),
+            stake,
+            register: true,
+            extra_stake: None,
+            service: None,
+            payment: None,
+            deregister: true,
+        }
+    }
+}
+
+/// Outcome of a single lifecycle step
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepOutcome {
+    pub step: LifecycleStep,
+    /// Transaction of the step; `None` if it failed or sent nothing
+    pub tx_hash: Option<H256>,
+    pub error: Option<String>,
+}
+
+impl StepOutcome {
+    fn from_result(step: LifecycleStep, result: Result<Option<H256>>) -> Self {
+        match result {
+            Ok(tx_hash) => Self { step, tx_hash, error: None },
+            Err(e) => Self { step, tx_hash: None, error: Some(e.to_string()) },
+        }
+    }
+    
+    pub fn succeeded(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Per-step results of a lifecycle run, in execution order
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LifecycleReport {
+    pub steps: Vec<StepOutcome>,
+}
+
+impl LifecycleReport {
+    pub fn all_succeeded(&self) -> bool {
+        self.steps.iter().all(StepOutcome::succeeded)
+    }
+}
+
+impl SynapseClient<SignerClient> {
+    /// Run the enabled steps of an agent lifecycle: approve, register, stake,
+    /// register a service, receive a payment and deregister.
+    ///
+    /// A failing step is recorded and the run continues with the next one,
+    /// so the report shows every step that was attempted.
+    pub async fn run_agent_lifecycle(&self, spec: LifecycleSpec) -> Result<LifecycleReport> {
+        let mut report = LifecycleReport::default();
+        
+        if spec.register {
+            let approve = self.approve_for_role(AgentRole::Provider).await
+                .map(|hashes| hashes.last().copied());
+            report.steps.push(StepOutcome::from_result(LifecycleStep::Approve, approve));
+            
+            let register = self.register_agent(&spec.agent_name, &spec.metadata_uri, spec.stake).await.map(Some);
+            report.steps.push(StepOutcome::from_result(LifecycleStep::Register, register));
+        }
+        
+        if let Some(amount) = spec.extra_stake {
+            let stake = self.increase_stake(amount).await.map(Some);
+            report.steps.push(StepOutcome::from_result(LifecycleStep::Stake, stake));
+        }
+        
+        if let Some(service) = &spec.service {
+            let registered = self.register_service(
+                &service.name,
+                &service.category,
+                &service.description,
+                &service.endpoint,
+                service.base_price,
+                service.pricing_model,
+            )
+            .await
+            .map(Some);
+            report.steps.push(StepOutcome::from_result(LifecycleStep::RegisterService, registered));
+        }
+        
+        if let Some(payment) = &spec.payment {
+            let received = self.receive_test_payment(payment).await.map(Some);
+            report.steps.push(StepOutcome::from_result(LifecycleStep::ReceivePayment, received));
+        }
+        
+        if spec.deregister {
+            let deregister = self.deregister_agent().await.map(Some);
+            report.steps.push(StepOutcome::from_result(LifecycleStep::Deregister, deregister));
+        }
+        
+        Ok(report)
+    }
+    
+    /// Have the payer wallet of `payment` pay this agent through the router
+    async fn receive_test_payment(&self, payment: &PaymentSpec) -> Result<H256> {
+        let payer = SynapseClient::new(
+            &self.config.rpc_url,
+            payment.payer_private_key.expose(),
+            self.config.contracts.clone(),
+        )
+        .await?;
+        
+        payer.approve_for_role(AgentRole::Payer).await?;
+        let result = payer.pay(self.address(), payment.amount, None).await?;
+        
+        Ok(result.tx_hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_lifecycle_spec_hides_payer_key() {
+        let key = "0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+        let mut spec = LifecycleSpec::new("agent", U256::from(100));
+        spec.payment = Some(PaymentSpec::new(key, U256::from(5)));
+        
+        assert!(!format!("{:?}", spec).contains(&key[2..]));
+        let json = serde_json::to_string(&spec).unwrap();
+        assert!(!json.contains(&key[2..]) && !json.contains("payment"));
+        let restored: LifecycleSpec = serde_json::from_str(&json).unwrap();
+        assert!(restored.payment.is_none() && restored.register && restored.deregister);
+        
+        let mut report = LifecycleReport::default();
+        report.steps.push(StepOutcome { step: LifecycleStep::Register, tx_hash: Some(H256::zero()), error: None });
+        assert!(report.all_succeeded());
+        report.steps.push(StepOutcome { step: LifecycleStep::ReceivePayment, tx_hash: None, error: Some("reverted".to_string()) });
+        assert!(!report.all_succeeded());
+        assert_eq!(serde_json::to_value(&report).unwrap()["steps"][1]["step"], "ReceivePayment");
+    }
+}